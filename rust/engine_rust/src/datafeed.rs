@@ -1,9 +1,83 @@
 // DataFeed: Market data management and benchmark timeline
 
 use crate::types::Bar;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
 use std::collections::HashMap;
 
+/// Parse a freqtrade-style timeframe string (`"1m"`, `"5m"`, `"15m"`, `"1h"`,
+/// `"4h"`, `"1d"`, ...) into its length in minutes.
+fn parse_timeframe_minutes(period: &str) -> Result<i64, String> {
+    let (digits, unit) = period.split_at(period.len().saturating_sub(1));
+    let count: i64 = digits
+        .parse()
+        .map_err(|_| format!("invalid timeframe '{}': expected e.g. \"5m\", \"1h\", \"1d\"", period))?;
+    match unit {
+        "m" => Ok(count),
+        "h" => Ok(count * 60),
+        "d" => Ok(count * 60 * 24),
+        _ => Err(format!("invalid timeframe '{}': expected e.g. \"5m\", \"1h\", \"1d\"", period)),
+    }
+}
+
+/// Floor `datetime` to the start of its resampling window, aligned to UTC
+/// midnight ("session boundary") rather than to the Unix epoch.
+fn bucket_start(datetime: DateTime<Utc>, period_minutes: i64) -> DateTime<Utc> {
+    let day_start = Utc.from_utc_datetime(&datetime.date_naive().and_hms_opt(0, 0, 0).unwrap());
+    if period_minutes >= 1440 {
+        let window_days = period_minutes / 1440;
+        let bucket_day = (datetime.date_naive().num_days_from_ce() as i64).div_euclid(window_days) * window_days;
+        Utc.from_utc_datetime(
+            &chrono::NaiveDate::from_num_days_from_ce_opt(bucket_day as i32)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        )
+    } else {
+        let minutes_since_day_start = (datetime - day_start).num_minutes();
+        day_start + Duration::minutes((minutes_since_day_start / period_minutes) * period_minutes)
+    }
+}
+
+/// Floor `datetime` to the start of the perpetual-futures funding window it
+/// falls in (e.g. the 00:00/08:00/16:00 UTC grid for `interval_hours =
+/// 8.0`), reusing the same UTC-midnight-aligned bucketing as `resample_bars`.
+pub fn funding_boundary(datetime: DateTime<Utc>, interval_hours: f64) -> DateTime<Utc> {
+    bucket_start(datetime, (interval_hours * 60.0).round().max(1.0) as i64)
+}
+
+/// Aggregate `bars` (ascending by `datetime`) into fixed-width windows of
+/// `period` (e.g. `"5m"`, `"1h"`, `"1d"`), aligned to UTC-midnight session
+/// boundaries: `open` is the window's first bar's open, `high`/`low` the
+/// window's max/min, `close` the window's last bar's close, and `volume` the
+/// window's summed volume. The final window may still be "forming" (its
+/// bars don't yet span the whole period) if `bars` ends mid-window — callers
+/// reading the latest resampled bar get freqtrade-style early visibility
+/// into the in-progress higher-timeframe candle.
+pub fn resample_bars(bars: &[Bar], period: &str) -> Result<Vec<Bar>, String> {
+    let period_minutes = parse_timeframe_minutes(period)?;
+    let mut resampled: Vec<Bar> = Vec::new();
+    for bar in bars {
+        let window_start = bucket_start(bar.datetime, period_minutes);
+        match resampled.last_mut() {
+            Some(last) if last.datetime == window_start => {
+                last.high = last.high.max(bar.high);
+                last.low = last.low.min(bar.low);
+                last.close = bar.close;
+                last.volume += bar.volume;
+            }
+            _ => resampled.push(Bar {
+                datetime: window_start,
+                open: bar.open,
+                high: bar.high,
+                low: bar.low,
+                close: bar.close,
+                volume: bar.volume,
+            }),
+        }
+    }
+    Ok(resampled)
+}
+
 // Make benchmark_timeline accessible
 impl DataFeed {
     pub fn benchmark_timeline(&self) -> &[DateTime<Utc>] {
@@ -20,6 +94,8 @@ pub struct DataFeed {
     symbol_indices: HashMap<String, usize>,
     // Cache: current bars to avoid repeated computation
     cached_current_bars: Option<(usize, HashMap<String, Bar>)>,
+    // Perpetual-futures funding schedule: symbol -> funding timestamp -> rate.
+    funding_rates: HashMap<String, HashMap<DateTime<Utc>, f64>>,
 }
 
 impl DataFeed {
@@ -31,9 +107,24 @@ impl DataFeed {
             current_index: 0,
             symbol_indices: HashMap::new(),
             cached_current_bars: None,
+            funding_rates: HashMap::new(),
         }
     }
 
+    /// Set `symbol`'s perpetual-futures funding-rate schedule: a rate to
+    /// charge/pay at each listed funding timestamp, looked up against the
+    /// `funding_interval_hours`-aligned boundary computed by
+    /// `funding_boundary`. Timestamps not present in the schedule charge 0.
+    pub fn set_funding_rates(&mut self, symbol: String, rates: Vec<(DateTime<Utc>, f64)>) {
+        self.funding_rates.insert(symbol, rates.into_iter().collect());
+    }
+
+    /// Look up `symbol`'s funding rate at the exact funding timestamp
+    /// `boundary` (see `funding_boundary`), or `None` if unset.
+    pub fn funding_rate_at(&self, symbol: &str, boundary: DateTime<Utc>) -> Option<f64> {
+        self.funding_rates.get(symbol).and_then(|schedule| schedule.get(&boundary)).copied()
+    }
+
     /// Add market data for a symbol
     pub fn add_market_data(&mut self, symbol: String, bars: Vec<Bar>) {
         // Sort bars by datetime
@@ -165,6 +256,49 @@ impl DataFeed {
         bars[start_idx..end_idx].to_vec()
     }
 
+    /// Get the last `count` bars of `symbol`'s data resampled up to `period`
+    /// (e.g. `"5m"`, `"1h"`, `"1d"`), using only bars at or before the
+    /// current timeline position (same look-ahead guard as `get_bars`).
+    pub fn get_bars_timeframe(&self, symbol: &str, period: &str, count: usize) -> Result<Vec<Bar>, String> {
+        let current_time = match self.get_current_datetime() {
+            Some(t) => t,
+            None => return Ok(Vec::new()),
+        };
+        let bars: Vec<Bar> = match self.market_data.get(symbol) {
+            Some(bars) => bars.iter().take_while(|b| b.datetime <= current_time).cloned().collect(),
+            None => return Ok(Vec::new()),
+        };
+        let resampled = resample_bars(&bars, period)?;
+        let start = resampled.len().saturating_sub(count);
+        Ok(resampled[start..].to_vec())
+    }
+
+    /// Like `get_bars_timeframe`, but returns only `symbol`'s most recent
+    /// `period` bar that has fully closed as of the current timeline
+    /// position — unlike `get_bars_timeframe`, which (per its doc comment)
+    /// may hand back a still-forming last window, this drops that window if
+    /// the current primary-timeframe timestamp still falls inside it. Used
+    /// to feed an "informative timeframe" to a strategy without look-ahead.
+    pub fn get_latest_closed_bar(&self, symbol: &str, period: &str) -> Result<Option<Bar>, String> {
+        let current_time = match self.get_current_datetime() {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let period_minutes = parse_timeframe_minutes(period)?;
+        let bars: Vec<Bar> = match self.market_data.get(symbol) {
+            Some(bars) => bars.iter().take_while(|b| b.datetime <= current_time).cloned().collect(),
+            None => return Ok(None),
+        };
+        let resampled = resample_bars(&bars, period)?;
+        let current_bucket = bucket_start(current_time, period_minutes);
+        Ok(match resampled.last() {
+            Some(last) if last.datetime == current_bucket => {
+                resampled.len().checked_sub(2).and_then(|i| resampled.get(i)).cloned()
+            }
+            last => last.cloned(),
+        })
+    }
+
     /// Get current bar for a symbol
     pub fn get_current_bar(&self, symbol: &str) -> Option<Bar> {
         if self.current_index >= self.benchmark_timeline.len() {
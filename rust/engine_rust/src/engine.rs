@@ -5,7 +5,8 @@ use crate::execution_engine::ExecutionEngine;
 use crate::indicator_engine::IndicatorEngine;
 use crate::metrics_recorder::MetricsRecorder;
 use crate::types::{Bar, Order, OrderSide, PortfolioState, QuantityType};
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use crate::universe::{UniverseCadence, UniverseFilter};
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Utc};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 use pyo3::wrap_pyfunction;
@@ -24,6 +25,44 @@ pub struct BacktestConfig {
     pub stamp_tax_rate: f64,
     pub t0_symbols: Vec<String>,
     pub period: Option<String>,
+    /// Cap on gross exposure (sum of `|market_value|` across positions) as a
+    /// multiple of equity. `f64::INFINITY` (the default) leaves leverage
+    /// bounded only by the per-symbol margin requirements already enforced by
+    /// `ExecutionEngine::set_margin_requirement`.
+    pub max_leverage: f64,
+    /// Per-bar financing rate charged against cash on gross short market
+    /// value (e.g. `0.0001` for 1bp/bar). Defaults to 0.0 so existing
+    /// long-only or unconfigured backtests are unaffected.
+    pub short_borrow_rate_per_bar: f64,
+    /// Enable perpetual-futures funding: at each `funding_interval_hours`
+    /// boundary, every open position is charged `rate * quantity * price *
+    /// 100` (the same lot-adjusted notional `calculate_equity` uses) against
+    /// cash, using the per-symbol schedule set via `set_funding_rates`.
+    /// Defaults to `false` so spot/equities backtests are unaffected.
+    pub futures_mode: bool,
+    /// Hours between funding events when `futures_mode` is enabled (e.g.
+    /// `8.0` for the common 00:00/08:00/16:00 UTC schedule). Ignored when
+    /// `futures_mode` is `false`.
+    pub funding_interval_hours: f64,
+    /// Execution model selector: `"fixed_bps"` (the default, `slippage_bps`
+    /// symmetric `SlippageModel`), `"volume_share"` (adds linear market
+    /// impact in order-size / bar-volume, scaled by `slippage_volume_k`, on
+    /// top of `slippage_bps` as the spread), or `"next_bar_open"` (keeps the
+    /// `fixed_bps` slippage model but defers every freshly submitted order to
+    /// fill at the next bar's open instead of this bar's close, avoiding
+    /// look-ahead — see `FillTiming`).
+    pub slippage_mode: String,
+    /// `k` coefficient for the `"volume_share"` slippage model. Ignored when
+    /// `slippage_mode` is `"fixed_bps"`.
+    pub slippage_volume_k: f64,
+    /// Flat per-share/per-contract commission, on top of `commission_rate`'s
+    /// percentage-of-notional charge. Defaults to 0.0 (unchanged behavior).
+    pub commission_per_share: f64,
+    /// Higher timeframes (e.g. `"1h"`) the primary series should be resampled
+    /// to, so strategies can read a slower trend filter alongside their
+    /// primary bars without manually resampling or guarding look-ahead
+    /// themselves. Empty by default. See `BacktestEngine::get_informative_bars`.
+    pub informative_timeframes: Vec<String>,
 }
 
 impl Default for BacktestConfig {
@@ -38,6 +77,14 @@ impl Default for BacktestConfig {
             stamp_tax_rate: 0.001,
             t0_symbols: Vec::new(),
             period: None,
+            max_leverage: f64::INFINITY,
+            short_borrow_rate_per_bar: 0.0,
+            futures_mode: false,
+            funding_interval_hours: 8.0,
+            slippage_mode: "fixed_bps".to_string(),
+            slippage_volume_k: 0.1,
+            commission_per_share: 0.0,
+            informative_timeframes: Vec::new(),
         }
     }
 }
@@ -52,16 +99,42 @@ pub struct BacktestEngine {
     pub(crate) metrics: MetricsRecorder,
     // Cache: current prices to avoid repeated computation
     cached_current_prices: Option<(usize, HashMap<String, f64>)>,
+    universe_filters: Vec<UniverseFilter>,
+    universe_cadence: UniverseCadence,
+    /// Symbols selected by the last `universe_filters` recompute; the per-bar
+    /// eligible set is this intersected with the current bar's tradable
+    /// symbols. Empty (meaning "no restriction") until filters are set.
+    universe: Vec<String>,
+    universe_last_recomputed: Option<chrono::NaiveDate>,
+    /// Funding window (see `datafeed::funding_boundary`) charged at the last
+    /// `record_equity` call, so each window is only charged once even though
+    /// `futures_mode` re-evaluates it every bar.
+    last_funding_boundary: Option<DateTime<Utc>>,
 }
 
 impl BacktestEngine {
     pub fn new(config: BacktestConfig) -> Self {
-        let execution_engine = ExecutionEngine::new(
+        let mut execution_engine = ExecutionEngine::new(
             config.commission_rate,
             config.min_commission,
             config.slippage_bps,
             config.stamp_tax_rate,
         );
+        execution_engine.set_max_leverage(config.max_leverage);
+        execution_engine.set_short_borrow_rate(config.short_borrow_rate_per_bar);
+        execution_engine.set_commission_per_share(config.commission_per_share);
+        match config.slippage_mode.as_str() {
+            "volume_share" => {
+                execution_engine.set_slippage_model(Box::new(crate::execution_engine::VolumeShare {
+                    spread_bps: config.slippage_bps,
+                    k: config.slippage_volume_k,
+                }));
+            }
+            "next_bar_open" => {
+                execution_engine.set_fill_timing(crate::execution_engine::FillTiming::NextBarOpen);
+            }
+            _ => {}
+        }
 
         let portfolio = PortfolioState::new(config.cash, config.t0_symbols.clone());
 
@@ -73,6 +146,11 @@ impl BacktestEngine {
             portfolio,
             metrics: MetricsRecorder::new(),
             cached_current_prices: None,
+            universe_filters: Vec::new(),
+            universe_cadence: UniverseCadence::EveryBar,
+            universe: Vec::new(),
+            universe_last_recomputed: None,
+            last_funding_boundary: None,
         }
     }
 
@@ -86,6 +164,31 @@ impl BacktestEngine {
         self.datafeed.set_benchmark(benchmark_bars);
     }
 
+    /// Set `symbol`'s perpetual-futures funding-rate schedule (see
+    /// `DataFeed::set_funding_rates`). Only consulted when `futures_mode` is
+    /// enabled in `BacktestConfig`.
+    pub fn set_funding_rates(&mut self, symbol: String, rates: Vec<(DateTime<Utc>, f64)>) {
+        self.datafeed.set_funding_rates(symbol, rates);
+    }
+
+    /// Charge perpetual-futures funding for every open position at the
+    /// funding `boundary` timestamp: a positive per-symbol rate means longs
+    /// pay shorts, so `rate * quantity * price * 100` (lot-adjusted notional)
+    /// is debited from cash — a short's negative `quantity` turns this into a
+    /// credit. Symbols with no rate registered for this `boundary` charge 0.
+    fn funding_charge(&self, boundary: DateTime<Utc>, current_bars: &HashMap<String, Bar>) -> f64 {
+        self.portfolio
+            .positions
+            .values()
+            .filter(|pos| pos.quantity != 0.0)
+            .filter_map(|pos| {
+                let rate = self.datafeed.funding_rate_at(&pos.symbol, boundary)?;
+                let price = current_bars.get(&pos.symbol).map(|b| b.close).unwrap_or(pos.avg_cost);
+                Some(rate * pos.quantity * price * 100.0)
+            })
+            .sum()
+    }
+
     /// Register indicator (called from Python)
     pub fn register_indicator(&self, name: String, def: crate::indicator_engine::IndicatorDef) {
         self.indicator_engine.borrow_mut().register_indicator(name, def);
@@ -126,14 +229,162 @@ impl BacktestEngine {
         engine.get_indicator_values(symbol, names)
     }
 
+    /// Get a named output of a multi-output indicator (e.g. `"middle"` /
+    /// `"upper"` / `"lower"` for `"bbands"`) for the current bar.
+    pub fn get_indicator_value_named(&self, name: &str, symbol: &str, output: &str) -> Option<f64> {
+        let engine = self.indicator_engine.borrow();
+        engine.get_indicator_value_named(name, symbol, output)
+    }
+
     /// Get bars for a symbol
     pub fn get_bars(&self, symbol: &str, count: usize) -> Vec<Bar> {
         self.datafeed.get_bars(symbol, count)
     }
 
-    /// Add order
-    pub fn add_order(&mut self, order: Order) {
+    /// Get bars for a symbol resampled up to `period` (e.g. `"5m"`, `"1d"`),
+    /// so a strategy stepping at the base resolution can still read
+    /// higher-timeframe bars without a separate dataset.
+    pub fn get_bars_timeframe(&self, symbol: &str, period: &str, count: usize) -> Result<Vec<Bar>, String> {
+        self.datafeed.get_bars_timeframe(symbol, period, count)
+    }
+
+    /// `symbol`'s most recently *closed* bar at `period`, with no visibility
+    /// into a still-forming window — see `DataFeed::get_latest_closed_bar`.
+    pub fn get_informative_bar(&self, symbol: &str, period: &str) -> Result<Option<Bar>, String> {
+        self.datafeed.get_latest_closed_bar(symbol, period)
+    }
+
+    /// The latest closed bar for `symbol` at every timeframe declared in
+    /// `BacktestConfig::informative_timeframes`, keyed by that timeframe
+    /// string. Lets a strategy read e.g. the most recent closed 1h candle
+    /// alongside its 5m primary bars without manually resampling or
+    /// guarding against look-ahead itself.
+    pub fn get_informative_bars(&self, symbol: &str) -> Result<HashMap<String, Bar>, String> {
+        let mut result = HashMap::new();
+        for timeframe in &self.config.informative_timeframes {
+            if let Some(bar) = self.datafeed.get_latest_closed_bar(symbol, timeframe)? {
+                result.insert(timeframe.clone(), bar);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Add order. Rejected if `symbol` is not in the currently-eligible
+    /// universe (see `set_universe_filters`); no-op restriction if no filters
+    /// are configured.
+    pub fn add_order(&mut self, order: Order) -> Result<(), String> {
+        if !self.is_symbol_eligible(&order.symbol) {
+            return Err(format!("symbol '{}' is filtered out of the current universe", order.symbol));
+        }
         self.execution_engine.add_order(order);
+        Ok(())
+    }
+
+    /// Configure the `UniverseFilter` chain and how often it is recomputed.
+    /// Takes effect starting with the next `update_universe` call.
+    pub fn set_universe_filters(&mut self, filters: Vec<UniverseFilter>, cadence: UniverseCadence) {
+        self.universe_filters = filters;
+        self.universe_cadence = cadence;
+        self.universe_last_recomputed = None;
+    }
+
+    /// Recompute (if due) the cadence-selected universe from `universe_filters`,
+    /// then intersect with this bar's tradable symbols (a halt or delisting is
+    /// always re-checked, regardless of cadence). Returns the resulting
+    /// per-bar eligible set. With no filters configured, returns every symbol
+    /// with a bar this period.
+    pub fn update_universe(&mut self) -> Vec<String> {
+        let current_bars = self.datafeed.get_current_bars();
+
+        if self.universe_filters.is_empty() {
+            return current_bars.keys().cloned().collect();
+        }
+
+        let current_date = self.datafeed.get_current_datetime().map(|dt| dt.date_naive());
+        let due = match (self.universe_cadence, self.universe_last_recomputed, current_date) {
+            (_, None, _) => true,
+            (UniverseCadence::EveryBar, _, _) => true,
+            (UniverseCadence::Daily, Some(last), Some(today)) => today != last,
+            (UniverseCadence::Weekly, Some(last), Some(today)) => today.iso_week() != last.iso_week(),
+            _ => true,
+        };
+
+        if due {
+            let candidates = self.datafeed.get_symbols();
+            let datafeed = &self.datafeed;
+            let indicator_engine = self.indicator_engine.borrow();
+            self.universe = crate::universe::apply_filters(
+                &self.universe_filters,
+                &candidates,
+                &current_bars,
+                |symbol, lookback| datafeed.get_bars(symbol, lookback),
+                |symbol, name| indicator_engine.get_indicator_value(name, symbol),
+            );
+            drop(indicator_engine);
+            self.universe_last_recomputed = current_date;
+        }
+
+        self.universe
+            .iter()
+            .filter(|symbol| current_bars.contains_key(symbol.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `symbol` is tradable this bar under the currently-effective
+    /// universe (always `true` when no filters are configured).
+    pub fn is_symbol_eligible(&self, symbol: &str) -> bool {
+        if self.universe_filters.is_empty() {
+            return true;
+        }
+        self.universe.iter().any(|s| s == symbol) && self.datafeed.get_current_bars().contains_key(symbol)
+    }
+
+    /// The effective per-bar universe as of the last `update_universe` call.
+    pub fn get_universe(&self) -> Vec<String> {
+        let current_bars = self.datafeed.get_current_bars();
+        if self.universe_filters.is_empty() {
+            return current_bars.keys().cloned().collect();
+        }
+        self.universe
+            .iter()
+            .filter(|symbol| current_bars.contains_key(symbol.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Rebalance the portfolio toward `targets` (symbol -> target weight) in a
+    /// single coherent pass and queue the resulting buy/sell orders.
+    pub fn rebalance_to_weights(&mut self, targets: HashMap<String, f64>) {
+        let current_bars = self.datafeed.get_current_bars();
+        let orders = self.execution_engine.rebalance_to_weights(&targets, &self.portfolio, &current_bars);
+        for order in orders {
+            self.execution_engine.add_order(order);
+        }
+    }
+
+    /// Attach (or replace) a protective-exit plan for `symbol`.
+    pub fn set_exit_plan(&mut self, symbol: String, plan: crate::types::ExitPlan) {
+        self.execution_engine.set_exit_plan(symbol, plan);
+    }
+
+    /// Detach `symbol`'s protective-exit plan, if any.
+    pub fn clear_exit_plan(&mut self, symbol: &str) {
+        self.execution_engine.clear_exit_plan(symbol);
+    }
+
+    /// Check every symbol's attached exit plan against this bar's intrabar
+    /// high/low and auto-generate fills for whichever trigger is breached.
+    /// Must be called before the strategy's `on_bar` callback runs.
+    pub fn check_protective_exits(&mut self) -> Vec<crate::types::Fill> {
+        let current_bars = self.datafeed.get_current_bars();
+        let bar_index = self.datafeed.current_index();
+        let fills = self.execution_engine.check_protective_exits(&current_bars, bar_index, &mut self.portfolio);
+        for fill in &fills {
+            self.portfolio.fills.push(fill.clone());
+        }
+        self.metrics.record_fills(fills.clone());
+        fills
     }
 
     /// Execute all orders for current bar
@@ -153,10 +404,40 @@ impl BacktestEngine {
     pub fn record_equity(&mut self) {
         use std::time::Instant;
         let start_time = Instant::now();
-        
+
         if let Some(datetime) = self.datafeed.get_current_datetime() {
             let current_index = self.datafeed.current_index();
-            
+
+            // Mark to market, then force-liquidate (largest maintenance-margin
+            // risk first) if the account has fallen below maintenance health.
+            let current_bars = self.datafeed.get_current_bars();
+            let liquidation_fills = self.execution_engine.liquidate_undermargined(&current_bars, &mut self.portfolio);
+            if !liquidation_fills.is_empty() {
+                for fill in &liquidation_fills {
+                    self.portfolio.fills.push(fill.clone());
+                }
+                self.metrics.record_fills(liquidation_fills);
+                self.cached_current_prices = None;
+            }
+
+            // Charge per-bar financing on gross short exposure before marking equity.
+            let financing_prices: HashMap<String, f64> = current_bars
+                .iter()
+                .map(|(s, b)| (s.clone(), b.close))
+                .collect();
+            self.execution_engine.accrue_short_financing(&financing_prices, &mut self.portfolio);
+
+            // Charge perpetual-futures funding once per funding window.
+            if self.config.futures_mode {
+                let boundary = crate::datafeed::funding_boundary(datetime, self.config.funding_interval_hours);
+                if self.last_funding_boundary != Some(boundary) {
+                    self.last_funding_boundary = Some(boundary);
+                    let charge = self.funding_charge(boundary, &current_bars);
+                    self.portfolio.cash -= charge;
+                    self.metrics.record_funding(charge);
+                }
+            }
+
             // Check cache for current prices
             let current_prices = if let Some((cached_idx, cached_prices)) = &self.cached_current_prices {
                 if *cached_idx == current_index {
@@ -234,6 +515,11 @@ impl BacktestEngine {
         self.metrics.calculate_stats()
     }
 
+    /// Get per-year or per-month performance breakdown
+    pub fn get_periodic_stats(&self, period: crate::types::Period) -> Vec<crate::types::PeriodStats> {
+        self.metrics.calculate_periodic_stats(period)
+    }
+
     /// Get equity curve
     pub fn get_equity_curve(&self) -> Vec<(String, f64)> {
         self.metrics
@@ -243,6 +529,11 @@ impl BacktestEngine {
             .collect()
     }
 
+    /// Get the FIFO round-trip trade ledger
+    pub fn get_round_trips(&self) -> Vec<crate::types::RoundTrip> {
+        self.metrics.get_round_trips()
+    }
+
     /// Get fills
     pub fn get_fills(&self) -> &[crate::types::Fill] {
         &self.portfolio.fills
@@ -270,12 +561,29 @@ pub struct PyBacktestConfig {
     pub t0_symbols: Vec<String>,
     #[pyo3(get, set)]
     pub period: Option<String>,
+    #[pyo3(get, set)]
+    pub max_leverage: f64,
+    #[pyo3(get, set)]
+    pub short_borrow_rate_per_bar: f64,
+    #[pyo3(get, set)]
+    pub futures_mode: bool,
+    #[pyo3(get, set)]
+    pub funding_interval_hours: f64,
+    #[pyo3(get, set)]
+    pub slippage_mode: String,
+    #[pyo3(get, set)]
+    pub slippage_volume_k: f64,
+    #[pyo3(get, set)]
+    pub commission_per_share: f64,
+    #[pyo3(get, set)]
+    pub informative_timeframes: Vec<String>,
 }
 
 #[pymethods]
 impl PyBacktestConfig {
     #[new]
-    #[pyo3(signature = (start=None, end=None, cash=None, commission_rate=None, min_commission=None, slippage_bps=None, stamp_tax_rate=None, t0_symbols=None, period=None))]
+    #[pyo3(signature = (start=None, end=None, cash=None, commission_rate=None, min_commission=None, slippage_bps=None, stamp_tax_rate=None, t0_symbols=None, period=None, max_leverage=None, short_borrow_rate_per_bar=None, futures_mode=None, funding_interval_hours=None, slippage_mode=None, slippage_volume_k=None, commission_per_share=None, informative_timeframes=None))]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         start: Option<String>,
         end: Option<String>,
@@ -286,6 +594,14 @@ impl PyBacktestConfig {
         stamp_tax_rate: Option<f64>,
         t0_symbols: Option<Vec<String>>,
         period: Option<String>,
+        max_leverage: Option<f64>,
+        short_borrow_rate_per_bar: Option<f64>,
+        futures_mode: Option<bool>,
+        funding_interval_hours: Option<f64>,
+        slippage_mode: Option<String>,
+        slippage_volume_k: Option<f64>,
+        commission_per_share: Option<f64>,
+        informative_timeframes: Option<Vec<String>>,
     ) -> Self {
         Self {
             start,
@@ -297,6 +613,14 @@ impl PyBacktestConfig {
             stamp_tax_rate: stamp_tax_rate.unwrap_or(0.001),
             t0_symbols: t0_symbols.unwrap_or_default(),
             period,
+            max_leverage: max_leverage.unwrap_or(f64::INFINITY),
+            short_borrow_rate_per_bar: short_borrow_rate_per_bar.unwrap_or(0.0),
+            futures_mode: futures_mode.unwrap_or(false),
+            funding_interval_hours: funding_interval_hours.unwrap_or(8.0),
+            slippage_mode: slippage_mode.unwrap_or_else(|| "fixed_bps".to_string()),
+            slippage_volume_k: slippage_volume_k.unwrap_or(0.1),
+            commission_per_share: commission_per_share.unwrap_or(0.0),
+            informative_timeframes: informative_timeframes.unwrap_or_default(),
         }
     }
 }
@@ -320,6 +644,14 @@ impl PyBacktestEngine {
             stamp_tax_rate: config.stamp_tax_rate,
             t0_symbols: config.t0_symbols.clone(),
             period: config.period.clone(),
+            max_leverage: config.max_leverage,
+            short_borrow_rate_per_bar: config.short_borrow_rate_per_bar,
+            futures_mode: config.futures_mode,
+            funding_interval_hours: config.funding_interval_hours,
+            slippage_mode: config.slippage_mode.clone(),
+            slippage_volume_k: config.slippage_volume_k,
+            commission_per_share: config.commission_per_share,
+            informative_timeframes: config.informative_timeframes.clone(),
         };
         Self {
             engine: BacktestEngine::new(rust_config),
@@ -338,8 +670,82 @@ impl PyBacktestEngine {
         Ok(())
     }
 
+    /// Set `symbol`'s perpetual-futures funding-rate schedule: `(timestamp,
+    /// rate)` pairs, `timestamp` as RFC3339 or `"YYYY-MM-DD HH:MM:SS"`. Only
+    /// consulted when `futures_mode` is enabled.
+    fn set_funding_rates(&mut self, symbol: String, rates: Vec<(String, f64)>) -> PyResult<()> {
+        let parsed = rates
+            .into_iter()
+            .map(|(ts, rate)| {
+                DateTime::parse_from_rfc3339(&ts)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .or_else(|_| {
+                        NaiveDateTime::parse_from_str(&ts, "%Y-%m-%d %H:%M:%S")
+                            .map(|naive| Utc.from_utc_datetime(&naive))
+                    })
+                    .map(|dt| (dt, rate))
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("invalid timestamp '{}': {}", ts, e)))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        self.engine.set_funding_rates(symbol, parsed);
+        Ok(())
+    }
+
+    /// Load OHLCV bars straight from a qlib-style on-disk dataset directory
+    /// (`calendars/day.txt` + `instruments/all.txt` + per-symbol/per-field
+    /// `.day.bin` files) without materializing Python `Bar` objects, and add
+    /// each loaded symbol's series via `add_market_data`. `symbols` empty
+    /// loads every instrument in `instruments/all.txt`; `fields` empty loads
+    /// open/high/low/close/volume. `start`/`end` are `"YYYY-MM-DD"` or `None`
+    /// for unbounded.
+    #[pyo3(signature = (path, symbols=None, fields=None, start=None, end=None))]
+    fn load_dataset(
+        &mut self,
+        path: String,
+        symbols: Option<Vec<String>>,
+        fields: Option<Vec<String>>,
+        start: Option<String>,
+        end: Option<String>,
+    ) -> PyResult<Vec<String>> {
+        let parse_date = |s: &str| {
+            chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+        };
+        let start = start.as_deref().map(parse_date).transpose()?;
+        let end = end.as_deref().map(parse_date).transpose()?;
+
+        let dataset = crate::qlib_store::load_dataset(
+            std::path::Path::new(&path),
+            &symbols.unwrap_or_default(),
+            &fields.unwrap_or_default(),
+            start,
+            end,
+        )
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+        let loaded: Vec<String> = dataset.keys().cloned().collect();
+        for (symbol, bars) in dataset {
+            self.engine.add_market_data(symbol, bars);
+        }
+        Ok(loaded)
+    }
+
+    /// Dump every symbol currently loaded in the engine's `DataFeed` to
+    /// `path` in the qlib-style layout `load_dataset` reads back, so a
+    /// dataset assembled once (e.g. from CSV via `add_market_data`) can be
+    /// reused across runs without re-parsing the source.
+    fn save_dataset(&self, path: String) -> PyResult<()> {
+        let mut data = HashMap::new();
+        for symbol in self.engine.datafeed.get_symbols() {
+            data.insert(symbol.clone(), self.engine.datafeed.get_all_bars_for_symbol(&symbol));
+        }
+        crate::qlib_store::write_dataset(std::path::Path::new(&path), &data)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
+    }
+
     fn get_current_bars(&self) -> PyResult<HashMap<String, PyBar>> {
-        let bars = self.engine.datafeed.get_current_bars();
+        let mut bars = self.engine.datafeed.get_current_bars();
+        bars.retain(|symbol, _| self.engine.is_symbol_eligible(symbol));
         Ok(bars.into_iter().map(|(k, v)| (k, PyBar::from(v))).collect())
     }
 
@@ -361,11 +767,43 @@ impl PyBacktestEngine {
         Ok(result)
     }
 
+    fn get_indicator_value_named(&self, name: String, symbol: String, output: String) -> Option<f64> {
+        self.engine.get_indicator_value_named(&name, &symbol, &output)
+    }
+
     fn get_bars(&self, symbol: String, count: usize) -> PyResult<Vec<PyBar>> {
         let bars = self.engine.get_bars(&symbol, count);
         Ok(bars.into_iter().map(PyBar::from).collect())
     }
 
+    fn get_bars_timeframe(&self, symbol: String, period: String, count: usize) -> PyResult<Vec<PyBar>> {
+        let bars = self
+            .engine
+            .get_bars_timeframe(&symbol, &period, count)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(bars.into_iter().map(PyBar::from).collect())
+    }
+
+    fn get_informative_bar(&self, symbol: String, period: String) -> PyResult<Option<PyBar>> {
+        let bar = self
+            .engine
+            .get_informative_bar(&symbol, &period)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(bar.map(PyBar::from))
+    }
+
+    /// The latest closed bar for `symbol` at every timeframe declared in
+    /// `PyBacktestConfig.informative_timeframes`, keyed by that timeframe —
+    /// for reading a slower trend filter from `on_bar` without manually
+    /// resampling or handling alignment/look-ahead.
+    fn get_informative_bars(&self, symbol: String) -> PyResult<HashMap<String, PyBar>> {
+        let bars = self
+            .engine
+            .get_informative_bars(&symbol)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(bars.into_iter().map(|(k, v)| (k, PyBar::from(v))).collect())
+    }
+
     fn has_next(&self) -> bool {
         self.engine.has_next()
     }
@@ -395,17 +833,71 @@ impl PyBacktestEngine {
             .get_current_datetime()
             .map(|dt| dt.date_naive())
             .unwrap_or_else(|| chrono::Utc::now().date_naive());
+        let current_bars = self.engine.datafeed.get_current_bars();
         for (symbol, pos) in &self.engine.portfolio.positions {
             let mut pos_dict = HashMap::new();
             pos_dict.insert("position".to_string(), pos.quantity);
             pos_dict.insert("available".to_string(), self.engine.portfolio.get_available(symbol, current_date));
             pos_dict.insert("avg_cost".to_string(), pos.avg_cost);
             pos_dict.insert("market_value".to_string(), pos.market_value);
+            // Liability is the cash needed to buy back a short at the current
+            // price; 0 for a flat or long position.
+            let liability = if pos.quantity < 0.0 {
+                let price = current_bars.get(symbol).map(|b| b.close).unwrap_or(pos.avg_cost);
+                pos.quantity.abs() * price * 100.0
+            } else {
+                0.0
+            };
+            pos_dict.insert("liability".to_string(), liability);
             result.insert(symbol.clone(), pos_dict);
         }
         Ok(result)
     }
 
+    /// Attach (or replace) a protective-exit plan for `symbol`: a fixed
+    /// stop-loss (`stop_loss_price` or `stop_loss_pct`), a take-profit
+    /// (`take_profit_price` or `take_profit_pct`), a trailing stop
+    /// (`trailing_stop_pct`, ratcheting with the best price seen since
+    /// entry), and/or a freqtrade-style ROI schedule: a list of
+    /// `(bars_held, min_return)` pairs, the most-elapsed applicable entry of
+    /// which forces an exit once that minimum return is reached. The price
+    /// triggers are checked against each bar's intrabar high/low and the ROI
+    /// schedule against its close, before `on_bar` runs.
+    #[pyo3(signature = (symbol, stop_loss_price=None, stop_loss_pct=None, take_profit_price=None, take_profit_pct=None, trailing_stop_pct=None, roi_schedule=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn set_exit_plan(
+        &mut self,
+        symbol: String,
+        stop_loss_price: Option<f64>,
+        stop_loss_pct: Option<f64>,
+        take_profit_price: Option<f64>,
+        take_profit_pct: Option<f64>,
+        trailing_stop_pct: Option<f64>,
+        roi_schedule: Option<Vec<(usize, f64)>>,
+    ) -> PyResult<()> {
+        use crate::types::ExitTrigger;
+        let stop_loss = stop_loss_price
+            .map(ExitTrigger::Price)
+            .or(stop_loss_pct.map(ExitTrigger::Percent));
+        let take_profit = take_profit_price
+            .map(ExitTrigger::Price)
+            .or(take_profit_pct.map(ExitTrigger::Percent));
+        let plan = crate::types::ExitPlan {
+            stop_loss,
+            take_profit,
+            trailing_stop_pct,
+            roi_schedule: roi_schedule.unwrap_or_default(),
+        };
+        self.engine.set_exit_plan(symbol, plan);
+        Ok(())
+    }
+
+    /// Detach `symbol`'s protective-exit plan, if any.
+    fn clear_exit_plan(&mut self, symbol: String) -> PyResult<()> {
+        self.engine.clear_exit_plan(&symbol);
+        Ok(())
+    }
+
     fn add_order(&mut self, symbol: String, side: String, quantity: f64, quantity_type: String) -> PyResult<()> {
         let order_side = match side.as_str() {
             "buy" => OrderSide::Buy,
@@ -423,15 +915,78 @@ impl PyBacktestEngine {
         let datetime = self.engine.datafeed.get_current_datetime()
             .unwrap_or_else(|| Utc::now());
 
-        let order = Order {
-            symbol,
-            side: order_side,
-            quantity_type: qty_type,
-            quantity,
-            timestamp: datetime,
+        let order = Order::market(symbol, order_side, qty_type, quantity, datetime);
+
+        self.engine
+            .add_order(order)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    /// Configure the dynamic universe filter chain (a list of dicts, each
+    /// with a `"type"` key of `"min_price"`, `"min_average_volume"`, or
+    /// `"top_n_by_indicator"` plus that filter's parameters) and how often
+    /// it's recomputed (`"every_bar"`, `"daily"`, or `"weekly"`). Tradability
+    /// (a missing current bar) is always re-checked every bar regardless.
+    #[pyo3(signature = (filters, cadence="every_bar".to_string()))]
+    fn set_universe_filters(&mut self, filters: Vec<HashMap<String, String>>, cadence: String) -> PyResult<()> {
+        let cadence = match cadence.as_str() {
+            "every_bar" => UniverseCadence::EveryBar,
+            "daily" => UniverseCadence::Daily,
+            "weekly" => UniverseCadence::Weekly,
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "unknown cadence '{}', expected 'every_bar', 'daily', or 'weekly'",
+                    other
+                )))
+            }
         };
 
-        self.engine.add_order(order);
+        let parsed = filters
+            .into_iter()
+            .map(|f| {
+                let get = |key: &str| f.get(key).cloned();
+                let parse_f64 = |key: &str| -> PyResult<f64> {
+                    get(key)
+                        .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("missing '{}'", key)))?
+                        .parse::<f64>()
+                        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+                };
+                let parse_usize = |key: &str| -> PyResult<usize> {
+                    get(key)
+                        .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("missing '{}'", key)))?
+                        .parse::<usize>()
+                        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+                };
+                match f.get("type").map(|s| s.as_str()) {
+                    Some("min_price") => Ok(UniverseFilter::MinPrice { min_price: parse_f64("min_price")? }),
+                    Some("min_average_volume") => Ok(UniverseFilter::MinAverageVolume {
+                        lookback: parse_usize("lookback")?,
+                        min_volume: parse_f64("min_volume")?,
+                    }),
+                    Some("top_n_by_indicator") => Ok(UniverseFilter::TopNByIndicator {
+                        indicator: get("indicator")
+                            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("missing 'indicator'"))?,
+                        n: parse_usize("n")?,
+                    }),
+                    other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "unknown filter type '{:?}'",
+                        other
+                    ))),
+                }
+            })
+            .collect::<PyResult<Vec<UniverseFilter>>>()?;
+
+        self.engine.set_universe_filters(parsed, cadence);
+        Ok(())
+    }
+
+    /// The currently-eligible symbol universe, as of the last bar's recompute.
+    fn get_universe(&self) -> Vec<String> {
+        self.engine.get_universe()
+    }
+
+    fn rebalance_to_weights(&mut self, targets: HashMap<String, f64>) -> PyResult<()> {
+        self.engine.rebalance_to_weights(targets);
         Ok(())
     }
 
@@ -458,11 +1013,14 @@ impl PyBacktestEngine {
                 dict.set_item("max_drawdown_end", end.to_rfc3339())?;
             }
             dict.set_item("sharpe_ratio", stats.sharpe_ratio)?;
+            dict.set_item("sortino_ratio", stats.sortino_ratio)?;
             dict.set_item("win_rate", stats.win_rate)?;
             dict.set_item("profit_loss_ratio", stats.profit_loss_ratio)?;
+            dict.set_item("profit_factor", stats.profit_factor)?;
+            dict.set_item("expectancy", stats.expectancy)?;
             dict.set_item("open_count", stats.open_count)?;
             dict.set_item("close_count", stats.close_count)?;
-            
+
             // Benchmark statistics
             if let Some(benchmark_return) = stats.benchmark_return {
                 dict.set_item("benchmark_return", benchmark_return)?;
@@ -479,26 +1037,104 @@ impl PyBacktestEngine {
             if let Some(end) = stats.benchmark_max_drawdown_end {
                 dict.set_item("benchmark_max_drawdown_end", end.to_rfc3339())?;
             }
-            
+            if let Some(beta) = stats.beta {
+                dict.set_item("beta", beta)?;
+            }
+            if let Some(alpha) = stats.alpha {
+                dict.set_item("alpha", alpha)?;
+            }
+            if let Some(tracking_error) = stats.tracking_error {
+                dict.set_item("tracking_error", tracking_error)?;
+            }
+            if let Some(information_ratio) = stats.information_ratio {
+                dict.set_item("information_ratio", information_ratio)?;
+            }
+            dict.set_item("avg_holding_period_days", stats.avg_holding_period_days)?;
+            dict.set_item("largest_win", stats.largest_win)?;
+            dict.set_item("largest_loss", stats.largest_loss)?;
+            dict.set_item("max_win_streak", stats.max_win_streak)?;
+            dict.set_item("max_loss_streak", stats.max_loss_streak)?;
+            dict.set_item("avg_win", stats.avg_win)?;
+            dict.set_item("avg_loss", stats.avg_loss)?;
+            dict.set_item("calmar_ratio", stats.calmar_ratio)?;
+            dict.set_item("trading_pnl", stats.trading_pnl)?;
+            dict.set_item("funding_pnl", stats.funding_pnl)?;
+            dict.set_item("total_funding_cost", stats.total_funding_cost)?;
+            dict.set_item("total_commission", stats.total_commission)?;
+            dict.set_item("total_slippage", stats.total_slippage)?;
+
             Ok(dict.into())
         })
     }
 
+    /// Per-year ("yearly") or per-month ("monthly") performance breakdown,
+    /// each entry a dict with label/return/max_drawdown/benchmark fields.
+    fn get_periodic_stats(&self, period: String) -> PyResult<Vec<PyObject>> {
+        let period = match period.as_str() {
+            "yearly" => crate::types::Period::Yearly,
+            "monthly" => crate::types::Period::Monthly,
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "unknown period '{}', expected 'yearly' or 'monthly'",
+                    other
+                )))
+            }
+        };
+        let stats = self.engine.get_periodic_stats(period);
+        Python::with_gil(|py| {
+            stats
+                .into_iter()
+                .map(|s| {
+                    let dict = PyDict::new_bound(py);
+                    dict.set_item("label", s.label)?;
+                    dict.set_item("return_pct", s.return_pct)?;
+                    dict.set_item("max_drawdown", s.max_drawdown)?;
+                    if let Some(benchmark_return) = s.benchmark_return {
+                        dict.set_item("benchmark_return", benchmark_return)?;
+                    }
+                    if let Some(excess_return) = s.excess_return {
+                        dict.set_item("excess_return", excess_return)?;
+                    }
+                    Ok(dict.into())
+                })
+                .collect()
+        })
+    }
+
     fn get_equity_curve(&self) -> Vec<(String, f64)> {
         self.engine.get_equity_curve()
     }
 
-    fn register_indicator(&self, name: String, indicator_type: String, params: HashMap<String, String>, lookback_period: usize) -> PyResult<()> {
+    /// `callback` is required when `indicator_type` is `"python_function"`:
+    /// a callable invoked as `callback(close_values, lookback_period) ->
+    /// list[float]` once per symbol during `compute_all_indicators`.
+    #[pyo3(signature = (name, indicator_type, params, lookback_period, timeframe=None, callback=None))]
+    fn register_indicator(
+        &self,
+        name: String,
+        indicator_type: String,
+        params: HashMap<String, String>,
+        lookback_period: usize,
+        timeframe: Option<String>,
+        callback: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
         use crate::indicator_engine::IndicatorDef;
         let def = match indicator_type.as_str() {
             "rust_builtin" => IndicatorDef::RustBuiltin {
                 name: params.get("name").cloned().unwrap_or_else(|| "unknown".to_string()),
                 params,
                 lookback_period,
+                timeframe,
             },
             "python_function" => IndicatorDef::PythonFunction {
                 name: params.get("name").cloned().unwrap_or_else(|| "unknown".to_string()),
+                callback: callback.ok_or_else(|| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                        "python_function indicators require a callback",
+                    )
+                })?,
                 lookback_period,
+                timeframe,
             },
             _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown indicator type: {}", indicator_type))),
         };
@@ -520,9 +1156,14 @@ impl PyBacktestEngine {
         Ok(fills.iter().map(|f| PyFill::from(f.clone())).collect())
     }
 
+    fn get_round_trips(&self) -> PyResult<Vec<PyRoundTrip>> {
+        let round_trips = self.engine.get_round_trips();
+        Ok(round_trips.into_iter().map(PyRoundTrip::from).collect())
+    }
+
     /// Run backtest with strategy callbacks
     /// Main loop is executed in Rust for better performance
-    fn run_backtest(
+    pub(crate) fn run_backtest(
         this: &Bound<'_, Self>,
         py: Python,
         strategy: &Bound<'_, PyAny>,
@@ -561,12 +1202,20 @@ impl PyBacktestEngine {
         
         while self_ref.engine.has_next() {
             bar_count += 1;
-            
+
+            // Recompute the eligible universe (if filters are configured)
+            // before orders execute or on_bar runs, so both only see
+            // currently-eligible symbols.
+            self_ref.engine.update_universe();
+
             // Execute orders first (needs mutable access)
             let order_start = Instant::now();
-            let fills = self_ref.engine.execute_orders();
+            let mut fills = self_ref.engine.execute_orders();
+            // Auto-exit any position whose stop-loss/take-profit/trailing-stop/ROI
+            // trigger this bar's intrabar high/low breached, before on_bar runs.
+            fills.extend(self_ref.engine.check_protective_exits());
             order_execution_time += order_start.elapsed();
-            
+
             // Then create context and call Python callbacks (releases mutable borrow)
             {
                 // Drop mutable borrow before calling Python
@@ -594,6 +1243,7 @@ impl PyBacktestEngine {
                         dict.set_item("filled_quantity", fill.quantity)?;
                         dict.set_item("price", fill.price)?;
                         dict.set_item("commission", fill.commission)?;
+                        dict.set_item("slippage", fill.slippage)?;
                         dict.set_item("timestamp", fill.timestamp.to_rfc3339())?;
                         dict.into()
                     };
@@ -665,11 +1315,14 @@ impl PyBacktestEngine {
             stats_dict.set_item("max_drawdown_end", end.to_rfc3339())?;
         }
         stats_dict.set_item("sharpe_ratio", stats_result.sharpe_ratio)?;
+        stats_dict.set_item("sortino_ratio", stats_result.sortino_ratio)?;
         stats_dict.set_item("win_rate", stats_result.win_rate)?;
         stats_dict.set_item("profit_loss_ratio", stats_result.profit_loss_ratio)?;
+        stats_dict.set_item("profit_factor", stats_result.profit_factor)?;
+        stats_dict.set_item("expectancy", stats_result.expectancy)?;
         stats_dict.set_item("open_count", stats_result.open_count)?;
         stats_dict.set_item("close_count", stats_result.close_count)?;
-        
+
         // Benchmark statistics
         if let Some(benchmark_return) = stats_result.benchmark_return {
             stats_dict.set_item("benchmark_return", benchmark_return)?;
@@ -686,10 +1339,43 @@ impl PyBacktestEngine {
         if let Some(end) = stats_result.benchmark_max_drawdown_end {
             stats_dict.set_item("benchmark_max_drawdown_end", end.to_rfc3339())?;
         }
-        
+        if let Some(beta) = stats_result.beta {
+            stats_dict.set_item("beta", beta)?;
+        }
+        if let Some(alpha) = stats_result.alpha {
+            stats_dict.set_item("alpha", alpha)?;
+        }
+        if let Some(tracking_error) = stats_result.tracking_error {
+            stats_dict.set_item("tracking_error", tracking_error)?;
+        }
+        if let Some(information_ratio) = stats_result.information_ratio {
+            stats_dict.set_item("information_ratio", information_ratio)?;
+        }
+        stats_dict.set_item("avg_holding_period_days", stats_result.avg_holding_period_days)?;
+        stats_dict.set_item("largest_win", stats_result.largest_win)?;
+        stats_dict.set_item("largest_loss", stats_result.largest_loss)?;
+        stats_dict.set_item("max_win_streak", stats_result.max_win_streak)?;
+        stats_dict.set_item("max_loss_streak", stats_result.max_loss_streak)?;
+        stats_dict.set_item("avg_win", stats_result.avg_win)?;
+        stats_dict.set_item("avg_loss", stats_result.avg_loss)?;
+        stats_dict.set_item("calmar_ratio", stats_result.calmar_ratio)?;
+        stats_dict.set_item("trading_pnl", stats_result.trading_pnl)?;
+        stats_dict.set_item("funding_pnl", stats_result.funding_pnl)?;
+        stats_dict.set_item("total_funding_cost", stats_result.total_funding_cost)?;
+        stats_dict.set_item("total_commission", stats_result.total_commission)?;
+        stats_dict.set_item("total_slippage", stats_result.total_slippage)?;
+
+        let trades: Vec<PyRoundTrip> = self_immut
+            .engine
+            .get_round_trips()
+            .into_iter()
+            .map(PyRoundTrip::from)
+            .collect();
+
         result_dict.set_item("stats", stats_dict)?;
         result_dict.set_item("equity_curve", equity_curve)?;
-        
+        result_dict.set_item("trades", trades)?;
+
         Ok(result_dict.into())
     }
 }
@@ -775,20 +1461,27 @@ pub struct PyFill {
     #[pyo3(get, set)]
     pub commission: f64,
     #[pyo3(get, set)]
+    pub slippage: f64,
+    #[pyo3(get, set)]
     pub timestamp: String,
+    #[pyo3(get, set)]
+    pub realized_pnl: f64,
 }
 
 #[pymethods]
 impl PyFill {
     #[new]
-    fn new(symbol: String, side: String, quantity: f64, price: f64, commission: f64, timestamp: String) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    fn new(symbol: String, side: String, quantity: f64, price: f64, commission: f64, slippage: f64, timestamp: String, realized_pnl: f64) -> Self {
         Self {
             symbol,
             side,
             quantity,
             price,
             commission,
+            slippage,
             timestamp,
+            realized_pnl,
         }
     }
 }
@@ -804,7 +1497,88 @@ impl From<crate::types::Fill> for PyFill {
             quantity: fill.quantity,
             price: fill.price,
             commission: fill.commission,
+            slippage: fill.slippage,
             timestamp: fill.timestamp.to_rfc3339(),
+            realized_pnl: fill.realized_pnl,
+        }
+    }
+}
+
+// PyRoundTrip for Python interface
+#[pyclass]
+#[derive(Clone)]
+pub struct PyRoundTrip {
+    #[pyo3(get, set)]
+    pub symbol: String,
+    #[pyo3(get, set)]
+    pub side: String,
+    #[pyo3(get, set)]
+    pub entry_time: String,
+    #[pyo3(get, set)]
+    pub entry_price: f64,
+    #[pyo3(get, set)]
+    pub exit_time: String,
+    #[pyo3(get, set)]
+    pub exit_price: f64,
+    #[pyo3(get, set)]
+    pub quantity: f64,
+    #[pyo3(get, set)]
+    pub realized_pnl: f64,
+    #[pyo3(get, set)]
+    pub commission: f64,
+    #[pyo3(get, set)]
+    pub return_pct: f64,
+    #[pyo3(get, set)]
+    pub holding_period_days: f64,
+}
+
+#[pymethods]
+impl PyRoundTrip {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        symbol: String,
+        side: String,
+        entry_time: String,
+        entry_price: f64,
+        exit_time: String,
+        exit_price: f64,
+        quantity: f64,
+        realized_pnl: f64,
+        commission: f64,
+        return_pct: f64,
+        holding_period_days: f64,
+    ) -> Self {
+        Self {
+            symbol,
+            side,
+            entry_time,
+            entry_price,
+            exit_time,
+            exit_price,
+            quantity,
+            realized_pnl,
+            commission,
+            return_pct,
+            holding_period_days,
+        }
+    }
+}
+
+impl From<crate::types::RoundTrip> for PyRoundTrip {
+    fn from(round_trip: crate::types::RoundTrip) -> Self {
+        Self {
+            symbol: round_trip.symbol,
+            side: round_trip.side,
+            entry_time: round_trip.entry_time.to_rfc3339(),
+            entry_price: round_trip.entry_price,
+            exit_time: round_trip.exit_time.to_rfc3339(),
+            exit_price: round_trip.exit_price,
+            quantity: round_trip.quantity,
+            realized_pnl: round_trip.realized_pnl,
+            commission: round_trip.commission,
+            return_pct: round_trip.return_pct,
+            holding_period_days: round_trip.holding_period.num_seconds() as f64 / 86400.0,
         }
     }
 }
@@ -816,13 +1590,15 @@ pub fn register_module(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyBacktestEngine>()?;
     m.add_class::<PyBar>()?;
     m.add_class::<PyFill>()?;
-    
+    m.add_class::<PyRoundTrip>()?;
+
     // Register database functions
     m.add_function(wrap_pyfunction!(crate::database::get_market_data, m)?)?;
     m.add_function(wrap_pyfunction!(crate::database::save_klines, m)?)?;
     m.add_function(wrap_pyfunction!(crate::database::save_klines_from_csv, m)?)?;
     m.add_function(wrap_pyfunction!(crate::database::resample_klines, m)?)?;
     m.add_function(wrap_pyfunction!(crate::database::load_and_synthesize_klines, m)?)?;
+    m.add_function(wrap_pyfunction!(crate::expr::compute_expressions, m)?)?;
     
     Ok(())
 }
@@ -1,14 +1,125 @@
 // ExecutionEngine: Order execution and matching
 
-use crate::types::{Bar, BuyRecord, Fill, Order, OrderSide, PortfolioState, Position, QuantityType};
+use crate::types::{
+    Bar, BuyRecord, ExitPlan, ExitState, ExitTrigger, Fill, MarginLevel, Order, OrderSide, OrderType, PortfolioState,
+    Position, QuantityType, TimeInForce,
+};
 use std::collections::HashMap;
 
+/// Pluggable fill-price adjustment, selected at `ExecutionEngine` construction.
+/// `order_shares` is the requested trade size in shares (`quantity_lots * 100`).
+pub trait SlippageModel {
+    fn fill_price(&self, side: &OrderSide, base_price: f64, order_shares: f64, bar: &Bar) -> f64;
+}
+
+/// Symmetric linear adjustment by a fixed number of basis points (the engine's
+/// original, and still the default, behavior).
+pub struct FixedBps {
+    pub slippage_bps: f64,
+}
+
+impl SlippageModel for FixedBps {
+    fn fill_price(&self, side: &OrderSide, base_price: f64, _order_shares: f64, _bar: &Bar) -> f64 {
+        let sign = match side {
+            OrderSide::Buy => 1.0,
+            OrderSide::Sell => -1.0,
+        };
+        base_price * (1.0 + sign * self.slippage_bps / 10000.0)
+    }
+}
+
+/// Linear market impact in order-size / bar-volume, on top of a fixed spread.
+pub struct VolumeShare {
+    pub spread_bps: f64,
+    pub k: f64,
+}
+
+impl SlippageModel for VolumeShare {
+    fn fill_price(&self, side: &OrderSide, base_price: f64, order_shares: f64, bar: &Bar) -> f64 {
+        if bar.volume <= 0.0 {
+            return FixedBps { slippage_bps: self.spread_bps }.fill_price(side, base_price, order_shares, bar);
+        }
+        let sign = match side {
+            OrderSide::Buy => 1.0,
+            OrderSide::Sell => -1.0,
+        };
+        let participation = order_shares / bar.volume;
+        base_price * (1.0 + sign * (self.spread_bps / 10000.0 + self.k * participation))
+    }
+}
+
+/// Square-root market impact model: impact grows with the square root of
+/// order-size / bar-volume, which better matches observed impact curves for
+/// larger orders than a purely linear model.
+pub struct SquareRootImpact {
+    pub spread_bps: f64,
+    pub k: f64,
+}
+
+impl SlippageModel for SquareRootImpact {
+    fn fill_price(&self, side: &OrderSide, base_price: f64, order_shares: f64, bar: &Bar) -> f64 {
+        if bar.volume <= 0.0 {
+            return FixedBps { slippage_bps: self.spread_bps }.fill_price(side, base_price, order_shares, bar);
+        }
+        let sign = match side {
+            OrderSide::Buy => 1.0,
+            OrderSide::Sell => -1.0,
+        };
+        let impact = self.spread_bps / 10000.0 + self.k * (order_shares / bar.volume).sqrt();
+        base_price * (1.0 + sign * impact)
+    }
+}
+
+/// When new orders attempt to fill, relative to the bar they were submitted
+/// on. `SameBarClose` (the engine's original behavior) lets a freshly
+/// submitted market order fill at the close of the very bar the strategy
+/// just observed — a mild look-ahead. `NextBarOpen` instead defers every
+/// freshly submitted order (of any type) one full bar, so it is only
+/// evaluated against bars the strategy hadn't seen yet when it decided to
+/// trade, with market orders filling at that next bar's open.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum FillTiming {
+    #[default]
+    SameBarClose,
+    NextBarOpen,
+}
+
 pub struct ExecutionEngine {
     orders: Vec<Order>,
+    // Resting book: non-market orders that were not marketable on a prior bar
+    resting_orders: Vec<Order>,
     commission_rate: f64,
     min_commission: f64,
-    slippage_bps: f64,
+    // Flat per-share/per-contract commission, added to `commission_rate`'s
+    // percentage-of-notional charge before the `min_commission` floor.
+    commission_per_share: f64,
+    slippage_model: Box<dyn SlippageModel>,
     stamp_tax_rate: f64,
+    fill_timing: FillTiming,
+    // Per-symbol (init_margin, maint_margin) fractions; symbols without an entry
+    // default to (1.0, 1.0), i.e. fully cash-backed with no leverage.
+    margin_requirements: HashMap<String, (f64, f64)>,
+    // Rebalance tuning: trades smaller than this (in cash) are skipped, and this
+    // much equity is always held back as uninvested cash.
+    min_trade_volume: f64,
+    min_cash_assets: f64,
+    // Per-asset ceiling on `rebalance_to_weights` target value, as a fraction
+    // of the investable pool (1.0 = no cap beyond the pool itself).
+    max_asset_weight: f64,
+    // Cap on how much of a bar's volume a single order may consume (e.g. 0.1 =
+    // 10% of bar.volume); None means no cap (fill the full requested size).
+    max_participation_rate: Option<f64>,
+    // Cap on gross exposure (sum of |market_value|) as a multiple of equity,
+    // on top of the per-symbol margin requirements. f64::INFINITY = no cap.
+    max_leverage: f64,
+    // Per-bar financing rate charged against cash on gross short market value.
+    short_borrow_rate: f64,
+    // Per-symbol protective exits (stop-loss/take-profit/trailing-stop/ROI),
+    // set via `set_exit_plan` and checked every bar by `check_protective_exits`.
+    exit_plans: HashMap<String, ExitPlan>,
+    // Runtime state (trailing high/low-water mark, entry bar) for `exit_plans`,
+    // keyed the same way and reset whenever the position returns to flat.
+    exit_state: HashMap<String, ExitState>,
 }
 
 impl ExecutionEngine {
@@ -20,13 +131,121 @@ impl ExecutionEngine {
     ) -> Self {
         Self {
             orders: Vec::new(),
+            resting_orders: Vec::new(),
             commission_rate,
             min_commission,
-            slippage_bps,
+            commission_per_share: 0.0,
+            slippage_model: Box::new(FixedBps { slippage_bps }),
             stamp_tax_rate,
+            fill_timing: FillTiming::default(),
+            margin_requirements: HashMap::new(),
+            min_trade_volume: 0.0,
+            min_cash_assets: 0.0,
+            max_asset_weight: 1.0,
+            max_participation_rate: None,
+            max_leverage: f64::INFINITY,
+            short_borrow_rate: 0.0,
+            exit_plans: HashMap::new(),
+            exit_state: HashMap::new(),
         }
     }
 
+    /// Swap in a different fill-price model (`FixedBps` is the default).
+    pub fn set_slippage_model(&mut self, model: Box<dyn SlippageModel>) {
+        self.slippage_model = model;
+    }
+
+    /// Swap in a different fill-timing policy (`SameBarClose` is the default).
+    pub fn set_fill_timing(&mut self, timing: FillTiming) {
+        self.fill_timing = timing;
+    }
+
+    /// Flat per-share/per-contract commission, on top of `commission_rate`
+    /// (0.0 by default, i.e. purely percentage-of-notional commission).
+    pub fn set_commission_per_share(&mut self, commission_per_share: f64) {
+        self.commission_per_share = commission_per_share;
+    }
+
+    /// Cap any single fill to at most `rate` of the bar's volume (in shares);
+    /// the unfilled remainder is worked across subsequent bars. `None` disables
+    /// the cap (the previous, unconstrained behavior).
+    pub fn set_max_participation_rate(&mut self, rate: Option<f64>) {
+        self.max_participation_rate = rate;
+    }
+
+    /// Set the initial/maintenance margin fractions required to trade `symbol`.
+    /// Defaults to (1.0, 1.0) — full cash backing — when never set.
+    pub fn set_margin_requirement(&mut self, symbol: String, init_margin: f64, maint_margin: f64) {
+        self.margin_requirements.insert(symbol, (init_margin, maint_margin));
+    }
+
+    fn margin_requirement(&self, symbol: &str) -> (f64, f64) {
+        self.margin_requirements.get(symbol).copied().unwrap_or((1.0, 1.0))
+    }
+
+    /// Cap gross exposure (sum of `|market_value|`) to `max_leverage` times
+    /// equity, on top of per-symbol margin requirements. Defaults to
+    /// `f64::INFINITY` (no additional cap).
+    pub fn set_max_leverage(&mut self, max_leverage: f64) {
+        self.max_leverage = max_leverage;
+    }
+
+    /// Set the per-bar financing rate charged on gross short market value
+    /// (e.g. `0.0001` for 1bp/bar). Defaults to 0.0.
+    pub fn set_short_borrow_rate(&mut self, rate: f64) {
+        self.short_borrow_rate = rate;
+    }
+
+    /// Debit `portfolio.cash` by `short_borrow_rate * gross short market
+    /// value` at `current_prices`, returning the amount charged. A no-op when
+    /// `short_borrow_rate` is 0 (the default) or there are no short positions.
+    pub fn accrue_short_financing(&self, current_prices: &HashMap<String, f64>, portfolio: &mut PortfolioState) -> f64 {
+        if self.short_borrow_rate == 0.0 {
+            return 0.0;
+        }
+        let gross_short_value: f64 = portfolio
+            .positions
+            .values()
+            .filter(|pos| pos.quantity < 0.0)
+            .map(|pos| {
+                let price = current_prices.get(&pos.symbol).copied().unwrap_or(pos.avg_cost);
+                pos.quantity.abs() * price * 100.0
+            })
+            .sum();
+        let charge = gross_short_value * self.short_borrow_rate;
+        portfolio.cash -= charge;
+        charge
+    }
+
+    /// Configure the rebalancer used by `rebalance_to_weights`: trades smaller
+    /// than `min_trade_volume` cash are skipped, and `min_cash_assets` of equity
+    /// is always kept uninvested.
+    pub fn set_rebalance_params(&mut self, min_trade_volume: f64, min_cash_assets: f64) {
+        self.min_trade_volume = min_trade_volume;
+        self.min_cash_assets = min_cash_assets;
+    }
+
+    /// Cap any single asset's `rebalance_to_weights` target at `max_asset_weight`
+    /// of the investable pool, so one high-weight asset can't absorb cash meant
+    /// for others. 1.0 (the default) leaves a single asset free to take the
+    /// whole pool.
+    pub fn set_max_asset_weight(&mut self, max_asset_weight: f64) {
+        self.max_asset_weight = max_asset_weight;
+    }
+
+    /// Attach (or replace) a protective-exit plan for `symbol`, evaluated by
+    /// `check_protective_exits` every bar until the position returns to flat.
+    pub fn set_exit_plan(&mut self, symbol: String, plan: ExitPlan) {
+        self.exit_plans.insert(symbol.clone(), plan);
+        self.exit_state.remove(&symbol);
+    }
+
+    /// Detach `symbol`'s protective-exit plan, if any.
+    pub fn clear_exit_plan(&mut self, symbol: &str) {
+        self.exit_plans.remove(symbol);
+        self.exit_state.remove(symbol);
+    }
+
     /// Add an order
     pub fn add_order(&mut self, order: Order) {
         self.orders.push(order);
@@ -35,6 +254,7 @@ impl ExecutionEngine {
     /// Clear all orders
     pub fn clear_orders(&mut self) {
         self.orders.clear();
+        self.resting_orders.clear();
     }
 
     /// Execute all orders
@@ -45,6 +265,32 @@ impl ExecutionEngine {
     ) -> Vec<Fill> {
         let mut fills = Vec::new();
 
+        // Bring resting (previously unfilled, non-market) orders back into the book,
+        // dropping any Day orders that have rolled over to a new trading date.
+        let mut pending: Vec<Order> = std::mem::take(&mut self.resting_orders)
+            .into_iter()
+            .filter(|order| {
+                if order.time_in_force == TimeInForce::Day {
+                    if let Some(bar) = current_bars.get(&order.symbol) {
+                        return bar.datetime.date_naive() == order.timestamp.date_naive();
+                    }
+                }
+                true
+            })
+            .collect();
+
+        // In `NextBarOpen` mode, freshly submitted orders must wait out the
+        // bar they were decided on rather than being attempted immediately
+        // (see `FillTiming`); they're appended to the resting book below
+        // instead of here, so this bar only evaluates orders already on it.
+        let deferred_fresh = if self.fill_timing == FillTiming::NextBarOpen {
+            std::mem::take(&mut self.orders)
+        } else {
+            pending.append(&mut self.orders);
+            Vec::new()
+        };
+        self.orders = pending;
+
         // Sort orders: sell first, then buy
         self.orders.sort_by(|a, b| {
             match (&a.side, &b.side) {
@@ -54,11 +300,23 @@ impl ExecutionEngine {
             }
         });
 
+        let mut still_resting = Vec::new();
+
         // Execute sell orders first
         for order in &self.orders.clone() {
             if order.side == OrderSide::Sell {
-                if let Some(fill) = self.execute_order(order, current_bars, portfolio) {
-                    fills.push(fill);
+                match self.execute_order(order, current_bars, portfolio) {
+                    Some((fill, leftover)) => {
+                        fills.push(fill);
+                        if let Some(remainder) = leftover {
+                            still_resting.push(remainder);
+                        }
+                    }
+                    None => {
+                        if self.should_rest(order, current_bars) {
+                            still_resting.push(order.clone());
+                        }
+                    }
                 }
             }
         }
@@ -66,34 +324,119 @@ impl ExecutionEngine {
         // Execute buy orders
         for order in &self.orders.clone() {
             if order.side == OrderSide::Buy {
-                if let Some(fill) = self.execute_order(order, current_bars, portfolio) {
-                    fills.push(fill);
+                match self.execute_order(order, current_bars, portfolio) {
+                    Some((fill, leftover)) => {
+                        fills.push(fill);
+                        if let Some(remainder) = leftover {
+                            still_resting.push(remainder);
+                        }
+                    }
+                    None => {
+                        if self.should_rest(order, current_bars) {
+                            still_resting.push(order.clone());
+                        }
+                    }
                 }
             }
         }
 
         self.orders.clear();
+        still_resting.extend(deferred_fresh);
+        self.resting_orders = still_resting;
         fills
     }
 
-    /// Execute a single order
+    /// Whether an unfilled order should be kept on the resting book for the next
+    /// bar. Non-market orders always rest until triggered or expired; a market
+    /// order only rests when a participation-rate cap is in play, since that is
+    /// the only reason a market order can fail to fill outright.
+    fn should_rest(&self, order: &Order, current_bars: &HashMap<String, Bar>) -> bool {
+        if order.order_type == OrderType::Market && self.max_participation_rate.is_none() {
+            return false;
+        }
+        // Already-expired day orders should not be kept; re-check here in case the
+        // order was only just submitted on the current bar's date.
+        if order.time_in_force == TimeInForce::Day {
+            if let Some(bar) = current_bars.get(&order.symbol) {
+                if bar.datetime.date_naive() != order.timestamp.date_naive() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Decide whether `order` is marketable within `bar`, and if so at what (pre-slippage) price.
+    fn marketable_price(&self, order: &Order, bar: &Bar) -> Option<f64> {
+        match (&order.side, &order.order_type) {
+            (_, OrderType::Market) => Some(match self.fill_timing {
+                FillTiming::SameBarClose => bar.close,
+                FillTiming::NextBarOpen => bar.open,
+            }),
+            (OrderSide::Buy, OrderType::Limit { limit_price }) => {
+                if bar.low <= *limit_price {
+                    Some(limit_price.min(bar.open))
+                } else {
+                    None
+                }
+            }
+            (OrderSide::Sell, OrderType::Limit { limit_price }) => {
+                if bar.high >= *limit_price {
+                    Some(limit_price.max(bar.open))
+                } else {
+                    None
+                }
+            }
+            (OrderSide::Buy, OrderType::Stop { stop_price }) => {
+                if bar.high >= *stop_price {
+                    Some(stop_price.max(bar.open))
+                } else {
+                    None
+                }
+            }
+            (OrderSide::Sell, OrderType::Stop { stop_price }) => {
+                if bar.low <= *stop_price {
+                    Some(stop_price.min(bar.open))
+                } else {
+                    None
+                }
+            }
+            (OrderSide::Buy, OrderType::StopLimit { stop_price, limit_price }) => {
+                if bar.high >= *stop_price && bar.low <= *limit_price {
+                    Some(limit_price.min(stop_price.max(bar.open)))
+                } else {
+                    None
+                }
+            }
+            (OrderSide::Sell, OrderType::StopLimit { stop_price, limit_price }) => {
+                if bar.low <= *stop_price && bar.high >= *limit_price {
+                    Some(limit_price.max(stop_price.min(bar.open)))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Execute a single order, returning the `Fill` for whatever portion was
+    /// marketable this bar, plus a leftover order to requeue when a
+    /// participation-rate cap left part of the requested size unfilled.
     fn execute_order(
         &self,
         order: &Order,
         current_bars: &HashMap<String, Bar>,
         portfolio: &mut PortfolioState,
-    ) -> Option<Fill> {
+    ) -> Option<(Fill, Option<Order>)> {
         let bar = current_bars.get(&order.symbol)?;
-        let base_price = bar.close;
-
-        // Calculate fill price with slippage
-        let fill_price = self.calculate_fill_price(&order.side, base_price);
+        let base_price = self.marketable_price(order, bar)?;
 
-        // Calculate quantity based on quantity_type
-        let quantity_lots = match order.quantity_type {
+        // Calculate quantity based on quantity_type. Cash/Weight sizing uses the
+        // pre-slippage marketable price, since the slippage/impact models below
+        // need an order size to compute the actual fill price.
+        let requested_lots = match order.quantity_type {
             QuantityType::Count => order.quantity,
             QuantityType::Cash => {
-                let quantity_shares = order.quantity / fill_price;
+                let quantity_shares = order.quantity / base_price;
                 self.round_to_lot(quantity_shares)
             }
             QuantityType::Weight => {
@@ -109,70 +452,503 @@ impl ExecutionEngine {
                     .map(|p| p.market_value)
                     .unwrap_or(0.0);
                 let needed_value = target_value - current_position;
-                let quantity_shares = needed_value / fill_price;
+                let quantity_shares = needed_value / base_price;
                 self.round_to_lot(quantity_shares)
             }
         };
 
-        if quantity_lots <= 0.0 {
+        if requested_lots <= 0.0 {
             return None;
         }
 
-        // Validate order
-        match order.side {
-            OrderSide::Sell => {
-                let trade_date = order.timestamp.date_naive();
-                let available = portfolio.get_available(&order.symbol, trade_date);
-                if quantity_lots > available {
-                    return None; // Reject: insufficient position
-                }
-            }
-            OrderSide::Buy => {
-                let trade_amount = quantity_lots * fill_price * 100.0; // Convert lots to shares
-                let commission = self.calculate_commission(trade_amount, &order.side);
-                if trade_amount + commission > portfolio.cash {
-                    return None; // Reject: insufficient cash
-                }
+        // Cap the fill to the configured share of this bar's volume; anything
+        // left over is handed back to the caller to rest for the next bar.
+        let quantity_lots = match self.max_participation_rate {
+            Some(rate) => {
+                let allowed_lots = self.round_to_lot((rate * bar.volume * 100.0).max(0.0));
+                requested_lots.min(allowed_lots)
             }
+            None => requested_lots,
+        };
+
+        if quantity_lots <= 0.0 {
+            return None;
         }
 
-        // Execute the order
+        // Calculate fill price with slippage/market impact, sized to what will
+        // actually trade this bar.
+        let fill_price = self.calculate_fill_price(&order.side, base_price, quantity_lots * 100.0, bar);
+
         let trade_amount = quantity_lots * fill_price * 100.0; // Convert lots to shares
-        let commission = self.calculate_commission(trade_amount, &order.side);
+        let commission = self.calculate_commission(trade_amount, quantity_lots * 100.0, &order.side);
+        let slippage = Self::slippage_cost(&order.side, base_price, fill_price, quantity_lots * 100.0);
+        let current_prices = Self::current_prices_map(current_bars);
+        let (init_margin, maint_margin) = self.margin_requirement(&order.symbol);
 
-        match order.side {
+        // Sells beyond the existing long position open or extend a short rather
+        // than being rejected outright; both directions are instead gated by the
+        // initial-margin health check below, same as leveraged longs.
+        let quantity_delta = match order.side {
+            OrderSide::Buy => quantity_lots,
+            OrderSide::Sell => -quantity_lots,
+        };
+        let cash_delta = match order.side {
+            OrderSide::Buy => -(trade_amount + commission),
+            OrderSide::Sell => trade_amount - commission,
+        };
+        if !self.projected_health_ok(
+            portfolio,
+            &order.symbol,
+            quantity_delta,
+            fill_price,
+            cash_delta,
+            init_margin,
+            &current_prices,
+        ) {
+            return None; // Reject: would breach initial margin requirement
+        }
+
+        // Execute the order
+        let trade_date = order.timestamp.date_naive();
+        let realized_pnl = match order.side {
             OrderSide::Buy => {
                 portfolio.cash -= trade_amount + commission;
-                let trade_date = order.timestamp.date_naive();
-                portfolio.add_position(&order.symbol, quantity_lots, fill_price, trade_date);
+                let (lot_pnl, matched_lots) = portfolio.add_position(&order.symbol, quantity_lots, fill_price, trade_date, init_margin, maint_margin);
+                if matched_lots > 0.0 { lot_pnl - commission } else { 0.0 }
             }
             OrderSide::Sell => {
-                let released_cash = portfolio.reduce_position(&order.symbol, quantity_lots, fill_price);
+                let (released_cash, lot_pnl, matched_lots) = portfolio.reduce_position(&order.symbol, quantity_lots, fill_price, trade_date, init_margin, maint_margin);
                 portfolio.cash += released_cash - commission;
+                if matched_lots > 0.0 { lot_pnl - commission } else { 0.0 }
             }
-        }
+        };
 
-        Some(Fill {
+        let fill = Fill {
             symbol: order.symbol.clone(),
             side: order.side.clone(),
             quantity: quantity_lots,
             price: fill_price,
             commission,
+            slippage,
             timestamp: order.timestamp,
-        })
+            realized_pnl,
+        };
+
+        let remainder_lots = requested_lots - quantity_lots;
+        let leftover = if remainder_lots > 0.0 {
+            Some(Order {
+                symbol: order.symbol.clone(),
+                side: order.side.clone(),
+                quantity_type: QuantityType::Count,
+                quantity: remainder_lots,
+                timestamp: order.timestamp,
+                order_type: order.order_type.clone(),
+                time_in_force: order.time_in_force.clone(),
+                filled_quantity: order.filled_quantity + quantity_lots,
+            })
+        } else {
+            None
+        };
+
+        Some((fill, leftover))
     }
 
-    /// Calculate fill price with slippage
-    fn calculate_fill_price(&self, side: &OrderSide, base_price: f64) -> f64 {
-        match side {
-            OrderSide::Buy => base_price * (1.0 + self.slippage_bps / 10000.0),
-            OrderSide::Sell => base_price * (1.0 - self.slippage_bps / 10000.0),
+    /// Build a symbol -> close-price map from the current bars (shared by the
+    /// weight-sizing path and the margin health checks).
+    fn current_prices_map(current_bars: &HashMap<String, Bar>) -> HashMap<String, f64> {
+        current_bars.iter().map(|(s, b)| (s.clone(), b.close)).collect()
+    }
+
+    /// Whether applying `quantity_delta` lots (signed: +buy/-sell) to `symbol` and
+    /// `cash_delta` to cash would keep the account at or above zero initial-margin
+    /// health, without mutating `portfolio`.
+    fn projected_health_ok(
+        &self,
+        portfolio: &PortfolioState,
+        symbol: &str,
+        quantity_delta: f64,
+        fill_price: f64,
+        cash_delta: f64,
+        init_margin: f64,
+        current_prices: &HashMap<String, f64>,
+    ) -> bool {
+        let mut equity = portfolio.cash + cash_delta;
+        let mut margin_requirement = 0.0;
+        let mut gross_exposure = 0.0;
+        let mut touched_symbol = false;
+
+        for pos in portfolio.positions.values() {
+            let price = current_prices.get(&pos.symbol).copied().unwrap_or(pos.avg_cost);
+            let (quantity, fraction) = if pos.symbol == symbol {
+                touched_symbol = true;
+                (pos.quantity + quantity_delta, init_margin)
+            } else {
+                (pos.quantity, pos.init_margin)
+            };
+            let market_value = quantity * price * 100.0;
+            equity += market_value;
+            margin_requirement += market_value.abs() * fraction;
+            gross_exposure += market_value.abs();
+        }
+
+        if !touched_symbol && quantity_delta != 0.0 {
+            let price = current_prices.get(symbol).copied().unwrap_or(fill_price);
+            let market_value = quantity_delta * price * 100.0;
+            equity += market_value;
+            margin_requirement += market_value.abs() * init_margin;
+            gross_exposure += market_value.abs();
         }
+
+        if equity - margin_requirement < 0.0 {
+            return false;
+        }
+        if self.max_leverage.is_finite() && gross_exposure > equity.max(0.0) * self.max_leverage {
+            return false;
+        }
+        true
     }
 
-    /// Calculate commission
-    fn calculate_commission(&self, trade_amount: f64, side: &OrderSide) -> f64 {
-        let base_commission = (trade_amount * self.commission_rate).max(self.min_commission);
+    /// Compute a coherent, portfolio-level set of orders that moves every symbol
+    /// in `targets` toward its target weight in a single pass, instead of sizing
+    /// each weight order independently against a moving equity base.
+    ///
+    /// Pass 1 (bottom-up): derive per-asset `[min_value, max_value]` bounds
+    /// (a floor of zero and a ceiling of `max_asset_weight` of the investable
+    /// pool). An asset whose target barely differs from its
+    /// current value (less than `min_trade_volume`) is pinned at its current
+    /// value so its cash stays put for the top-down pass.
+    ///
+    /// Pass 2 (top-down): the remaining `equity - min_cash_assets` (after
+    /// removing the cash already pinned to untouched assets) is distributed
+    /// across the non-pinned assets proportional to their weights, clamped to
+    /// `[min_value, max_value]`.
+    ///
+    /// Orders are only emitted where the resulting delta exceeds
+    /// `min_trade_volume`, and sells are returned before buys so that sell
+    /// proceeds are available to fund the same-bar buys.
+    pub fn rebalance_to_weights(
+        &self,
+        targets: &HashMap<String, f64>,
+        portfolio: &PortfolioState,
+        current_bars: &HashMap<String, Bar>,
+    ) -> Vec<Order> {
+        let current_prices = Self::current_prices_map(current_bars);
+        let equity = portfolio.calculate_equity(&current_prices);
+        let investable = (equity - self.min_cash_assets).max(0.0);
+
+        struct AssetPlan {
+            symbol: String,
+            price: f64,
+            weight: f64,
+            current_value: f64,
+            pinned: bool,
+            min_value: f64,
+            max_value: f64,
+        }
+
+        // Pass 1: bottom-up bounds. A weight order never shorts, so the floor is
+        // zero; the ceiling is `max_asset_weight` of the investable pool so a
+        // single asset can't absorb cash meant for others. Assets within the
+        // deadband of their current value are pinned instead of re-targeted.
+        let asset_max_value = investable * self.max_asset_weight;
+        let mut plans: Vec<AssetPlan> = Vec::new();
+        for (symbol, &weight) in targets {
+            let price = match current_prices.get(symbol) {
+                Some(&p) if p > 0.0 => p,
+                _ => continue, // no tradable price this bar, skip
+            };
+            let current_value = portfolio
+                .positions
+                .get(symbol)
+                .map(|pos| pos.quantity * price * 100.0)
+                .unwrap_or(0.0);
+            let raw_target = investable * weight;
+            let pinned = (raw_target - current_value).abs() < self.min_trade_volume;
+            plans.push(AssetPlan {
+                symbol: symbol.clone(),
+                price,
+                weight,
+                current_value,
+                pinned,
+                min_value: 0.0,
+                max_value: asset_max_value,
+            });
+        }
+
+        // Pass 2: top-down distribution of the non-pinned pool, proportional to weight.
+        let pinned_value: f64 = plans.iter().filter(|p| p.pinned).map(|p| p.current_value).sum();
+        let distributable = (investable - pinned_value).max(0.0);
+        let movable_weight: f64 = plans.iter().filter(|p| !p.pinned).map(|p| p.weight).sum();
+
+        let mut orders: Vec<Order> = Vec::new();
+        let mut sells: Vec<Order> = Vec::new();
+        let mut buys: Vec<Order> = Vec::new();
+
+        for plan in &plans {
+            let target_value = if plan.pinned {
+                plan.current_value
+            } else if movable_weight > 0.0 {
+                (distributable * (plan.weight / movable_weight)).clamp(plan.min_value, plan.max_value)
+            } else {
+                0.0
+            };
+
+            let delta_value = target_value - plan.current_value;
+            if delta_value.abs() < self.min_trade_volume {
+                continue;
+            }
+
+            let bar = match current_bars.get(&plan.symbol) {
+                Some(b) => b,
+                None => continue,
+            };
+            let quantity_lots = self.round_to_lot(delta_value.abs() / plan.price);
+            if quantity_lots <= 0.0 {
+                continue;
+            }
+
+            let side = if delta_value > 0.0 { OrderSide::Buy } else { OrderSide::Sell };
+            let order = Order::market(plan.symbol.clone(), side.clone(), QuantityType::Count, quantity_lots, bar.datetime);
+            match side {
+                OrderSide::Sell => sells.push(order),
+                OrderSide::Buy => buys.push(order),
+            }
+        }
+
+        orders.append(&mut sells);
+        orders.append(&mut buys);
+        orders
+    }
+
+    /// Mark positions to market and, if maintenance health is negative, emit
+    /// synthetic liquidation fills (largest-risk position first) until health
+    /// returns to non-negative or there is nothing left to close.
+    pub fn liquidate_undermargined(
+        &self,
+        current_bars: &HashMap<String, Bar>,
+        portfolio: &mut PortfolioState,
+    ) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let current_prices = Self::current_prices_map(current_bars);
+
+        loop {
+            if portfolio.account_health(&current_prices, MarginLevel::Maint) >= 0.0 {
+                break;
+            }
+
+            // Pick the position with the largest maintenance-margin risk that we
+            // can actually price and close this bar.
+            let target = portfolio
+                .positions
+                .values()
+                .filter(|pos| current_bars.contains_key(&pos.symbol))
+                .max_by(|a, b| {
+                    let risk = |pos: &Position| {
+                        let price = current_prices.get(&pos.symbol).copied().unwrap_or(pos.avg_cost);
+                        (pos.quantity * price * 100.0).abs() * pos.maint_margin
+                    };
+                    risk(a).partial_cmp(&risk(b)).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .map(|pos| (pos.symbol.clone(), pos.quantity, pos.init_margin, pos.maint_margin));
+
+            let (symbol, quantity, init_margin, maint_margin) = match target {
+                Some(t) => t,
+                None => break, // nothing left with a known price to liquidate
+            };
+
+            let bar = match current_bars.get(&symbol) {
+                Some(b) => b,
+                None => break,
+            };
+
+            let side = if quantity > 0.0 { OrderSide::Sell } else { OrderSide::Buy };
+            let close_quantity = quantity.abs();
+            let fill_price = self.calculate_fill_price(&side, bar.close, close_quantity * 100.0, bar);
+            let trade_amount = close_quantity * fill_price * 100.0;
+            let commission = self.calculate_commission(trade_amount, close_quantity * 100.0, &side);
+            let slippage = Self::slippage_cost(&side, bar.close, fill_price, close_quantity * 100.0);
+            let trade_date = bar.datetime.date_naive();
+
+            let (lot_pnl, matched_lots) = match side {
+                OrderSide::Sell => {
+                    let (released_cash, lot_pnl, matched_lots) = portfolio.reduce_position(&symbol, close_quantity, fill_price, trade_date, init_margin, maint_margin);
+                    portfolio.cash += released_cash - commission;
+                    (lot_pnl, matched_lots)
+                }
+                OrderSide::Buy => {
+                    portfolio.cash -= trade_amount + commission;
+                    portfolio.add_position(&symbol, close_quantity, fill_price, trade_date, init_margin, maint_margin)
+                }
+            };
+            let realized_pnl = if matched_lots > 0.0 { lot_pnl - commission } else { 0.0 };
+
+            fills.push(Fill {
+                symbol,
+                side,
+                quantity: close_quantity,
+                price: fill_price,
+                commission,
+                slippage,
+                timestamp: bar.datetime,
+                realized_pnl,
+            });
+        }
+
+        fills
+    }
+
+    /// Check every symbol with an attached `ExitPlan` against this bar's
+    /// intrabar high/low (not just its close) and auto-generate a market/stop/
+    /// limit fill for whichever trigger is breached first, in priority order
+    /// stop-loss, trailing-stop, take-profit, ROI schedule. Intended to be
+    /// called once per bar, before the strategy's `on_bar` callback runs.
+    pub fn check_protective_exits(
+        &mut self,
+        current_bars: &HashMap<String, Bar>,
+        bar_index: usize,
+        portfolio: &mut PortfolioState,
+    ) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let symbols: Vec<String> = self.exit_plans.keys().cloned().collect();
+
+        for symbol in symbols {
+            let pos = match portfolio.positions.get(&symbol) {
+                Some(p) if p.quantity != 0.0 => p.clone(),
+                _ => {
+                    self.exit_state.remove(&symbol);
+                    continue;
+                }
+            };
+            let bar = match current_bars.get(&symbol) {
+                Some(b) => b.clone(),
+                None => continue,
+            };
+            let plan = self.exit_plans.get(&symbol).expect("symbol came from exit_plans.keys()").clone();
+            let is_long = pos.quantity > 0.0;
+
+            let state = self.exit_state.entry(symbol.clone()).or_insert_with(|| ExitState {
+                opened_at_bar: bar_index,
+                extreme_price: pos.avg_cost,
+            });
+            state.extreme_price = if is_long {
+                state.extreme_price.max(bar.high)
+            } else {
+                state.extreme_price.min(bar.low)
+            };
+            let extreme_price = state.extreme_price;
+            let opened_at_bar = state.opened_at_bar;
+
+            let exit_side = if is_long { OrderSide::Sell } else { OrderSide::Buy };
+            let quantity = pos.quantity.abs();
+            let make_order = |order_type: OrderType| Order {
+                symbol: symbol.clone(),
+                side: exit_side.clone(),
+                quantity_type: QuantityType::Count,
+                quantity,
+                timestamp: bar.datetime,
+                order_type,
+                time_in_force: TimeInForce::Day,
+                filled_quantity: 0.0,
+            };
+
+            let mut triggered: Option<Order> = plan.stop_loss.map(|trigger| {
+                make_order(OrderType::Stop { stop_price: Self::stop_trigger_price(trigger, pos.avg_cost, is_long) })
+            }).filter(|order| self.marketable_price(order, &bar).is_some());
+
+            if triggered.is_none() {
+                triggered = plan.trailing_stop_pct.map(|pct| {
+                    let stop_price = if is_long { extreme_price * (1.0 - pct) } else { extreme_price * (1.0 + pct) };
+                    make_order(OrderType::Stop { stop_price })
+                }).filter(|order| self.marketable_price(order, &bar).is_some());
+            }
+
+            if triggered.is_none() {
+                triggered = plan.take_profit.map(|trigger| {
+                    make_order(OrderType::Limit { limit_price: Self::take_profit_trigger_price(trigger, pos.avg_cost, is_long) })
+                }).filter(|order| self.marketable_price(order, &bar).is_some());
+            }
+
+            if triggered.is_none() && !plan.roi_schedule.is_empty() {
+                let bars_held = bar_index.saturating_sub(opened_at_bar);
+                let applicable = plan.roi_schedule.iter().filter(|(b, _)| *b <= bars_held).max_by_key(|(b, _)| *b);
+                if let Some(&(_, min_return)) = applicable {
+                    let current_return = if is_long {
+                        (bar.close - pos.avg_cost) / pos.avg_cost
+                    } else {
+                        (pos.avg_cost - bar.close) / pos.avg_cost
+                    };
+                    if current_return >= min_return {
+                        triggered = Some(make_order(OrderType::Market));
+                    }
+                }
+            }
+
+            if let Some(order) = triggered {
+                if let Some((fill, _leftover)) = self.execute_order(&order, current_bars, portfolio) {
+                    fills.push(fill);
+                    self.exit_state.remove(&symbol);
+                }
+            }
+        }
+
+        fills
+    }
+
+    /// Resolve a stop-loss `ExitTrigger` to an absolute price: a `Percent`
+    /// trigger moves adverse to the position's direction.
+    fn stop_trigger_price(trigger: ExitTrigger, avg_cost: f64, is_long: bool) -> f64 {
+        match trigger {
+            ExitTrigger::Price(p) => p,
+            ExitTrigger::Percent(pct) => {
+                if is_long {
+                    avg_cost * (1.0 - pct)
+                } else {
+                    avg_cost * (1.0 + pct)
+                }
+            }
+        }
+    }
+
+    /// Resolve a take-profit `ExitTrigger` to an absolute price: a `Percent`
+    /// trigger moves favorable to the position's direction.
+    fn take_profit_trigger_price(trigger: ExitTrigger, avg_cost: f64, is_long: bool) -> f64 {
+        match trigger {
+            ExitTrigger::Price(p) => p,
+            ExitTrigger::Percent(pct) => {
+                if is_long {
+                    avg_cost * (1.0 + pct)
+                } else {
+                    avg_cost * (1.0 - pct)
+                }
+            }
+        }
+    }
+
+    /// Calculate fill price via the configured slippage/market-impact model
+    fn calculate_fill_price(&self, side: &OrderSide, base_price: f64, order_shares: f64, bar: &Bar) -> f64 {
+        self.slippage_model.fill_price(side, base_price, order_shares, bar)
+    }
+
+    /// The portion of this fill's cost attributable to slippage: how much
+    /// worse `fill_price` is than the pre-slippage `base_price` the order
+    /// would have gotten, in cash terms. Always non-negative for an
+    /// unfavorable model and non-positive for a favorable one, signed so it
+    /// nets against P&L the same way `commission` does.
+    fn slippage_cost(side: &OrderSide, base_price: f64, fill_price: f64, order_shares: f64) -> f64 {
+        let sign = match side {
+            OrderSide::Buy => 1.0,
+            OrderSide::Sell => -1.0,
+        };
+        sign * (fill_price - base_price) * order_shares
+    }
+
+    /// Calculate commission: `trade_amount * commission_rate` (percentage of
+    /// notional) plus `order_shares * commission_per_share` (flat per-share/
+    /// per-contract), floored at `min_commission`, plus the sell-side stamp
+    /// tax.
+    fn calculate_commission(&self, trade_amount: f64, order_shares: f64, side: &OrderSide) -> f64 {
+        let base_commission = (trade_amount * self.commission_rate + order_shares * self.commission_per_share)
+            .max(self.min_commission);
         match side {
             OrderSide::Buy => base_commission,
             OrderSide::Sell => base_commission + trade_amount * self.stamp_tax_rate,
@@ -187,24 +963,97 @@ impl ExecutionEngine {
 
 // Add methods to PortfolioState
 impl PortfolioState {
-    /// Add position
-    pub fn add_position(&mut self, symbol: &str, quantity: f64, price: f64, trade_date: chrono::NaiveDate) {
+    /// Match `incoming_qty` lots (always positive) FIFO against `lots`, which
+    /// holds only same-signed entries (positive = long lots, negative = short
+    /// lots) until fully drained. `is_buy` selects which side of the spread
+    /// realizes the gain: a buy covers short lots (`lot.price - fill_price`), a
+    /// sell closes long lots (`fill_price - lot.price`). Returns the gross
+    /// realized P&L and how much of `incoming_qty` was actually matched; any
+    /// unmatched remainder is the caller's responsibility to open as a new lot.
+    fn match_fifo_lots(lots: &mut Vec<BuyRecord>, incoming_qty: f64, fill_price: f64, is_buy: bool) -> (f64, f64) {
+        let mut remaining = incoming_qty;
+        let mut realized_pnl = 0.0;
+
+        while remaining > 0.0 {
+            let lot_quantity = match lots.first() {
+                Some(lot) => lot.quantity.abs(),
+                None => break,
+            };
+            let matched = remaining.min(lot_quantity);
+            let lot_price = lots[0].price;
+            realized_pnl += if is_buy {
+                matched * (lot_price - fill_price) * 100.0
+            } else {
+                matched * (fill_price - lot_price) * 100.0
+            };
+
+            remaining -= matched;
+            if matched >= lot_quantity {
+                lots.remove(0);
+            } else {
+                let sign = if lots[0].quantity > 0.0 { 1.0 } else { -1.0 };
+                lots[0].quantity = sign * (lot_quantity - matched);
+            }
+        }
+
+        (realized_pnl, incoming_qty - remaining)
+    }
+
+    /// Re-derive `positions[symbol]` from `open_lots[symbol]`: net quantity is
+    /// the signed sum of the (same-signed) open lots and `avg_cost` is their
+    /// size-weighted average price, keeping the reported position in lockstep
+    /// with the FIFO cost ledger that drives realized P&L.
+    fn sync_position_from_lots(&mut self, symbol: &str, init_margin: f64, maint_margin: f64) {
+        let lots = self.open_lots.get(symbol);
+        let net_quantity: f64 = lots.map(|l| l.iter().map(|r| r.quantity).sum()).unwrap_or(0.0);
+
+        if net_quantity == 0.0 {
+            self.positions.remove(symbol);
+            self.open_lots.remove(symbol);
+            return;
+        }
+
+        let total_abs: f64 = lots.map(|l| l.iter().map(|r| r.quantity.abs()).sum()).unwrap_or(0.0);
+        let weighted_cost: f64 = lots
+            .map(|l| l.iter().map(|r| r.quantity.abs() * r.price).sum())
+            .unwrap_or(0.0);
+        let avg_cost = if total_abs > 0.0 { weighted_cost / total_abs } else { 0.0 };
+
         let position = self.positions.entry(symbol.to_string()).or_insert(Position {
             symbol: symbol.to_string(),
             quantity: 0.0,
             avg_cost: 0.0,
             market_value: 0.0,
             available: 0.0,
+            init_margin,
+            maint_margin,
         });
+        position.quantity = net_quantity;
+        position.avg_cost = avg_cost;
+        position.init_margin = init_margin;
+        position.maint_margin = maint_margin;
+    }
 
-        let total_cost = position.quantity * position.avg_cost * 100.0 + quantity * price * 100.0;
-        let total_quantity = position.quantity + quantity;
-        position.quantity = total_quantity;
-        position.avg_cost = if total_quantity > 0.0 {
-            total_cost / (total_quantity * 100.0)
-        } else {
-            0.0
-        };
+    /// Add to a position (a buy). `quantity` is always positive; if the position
+    /// is currently short this covers it FIFO (and may flip it net long),
+    /// realizing P&L per matched lot. Returns `(realized_pnl, matched_quantity)`.
+    pub fn add_position(
+        &mut self,
+        symbol: &str,
+        quantity: f64,
+        price: f64,
+        trade_date: chrono::NaiveDate,
+        init_margin: f64,
+        maint_margin: f64,
+    ) -> (f64, f64) {
+        let lots = self.open_lots.entry(symbol.to_string()).or_insert_with(Vec::new);
+        let (realized_pnl, matched_quantity) = Self::match_fifo_lots(lots, quantity, price, true);
+        let remainder = quantity - matched_quantity;
+        if remainder > 0.0 {
+            lots.push(BuyRecord { date: trade_date, quantity: remainder, price });
+        }
+        self.realized_pnl += realized_pnl;
+        self.sync_position_from_lots(symbol, init_margin, maint_margin);
 
         // Record buy for T+1 tracking
         if !self.t0_symbols.contains(&symbol.to_string()) {
@@ -215,26 +1064,35 @@ impl PortfolioState {
                 price,
             });
         }
-    }
 
-    /// Reduce position
-    pub fn reduce_position(&mut self, symbol: &str, quantity: f64, price: f64) -> f64 {
-        let position = match self.positions.get_mut(symbol) {
-            Some(p) => p,
-            None => return 0.0,
-        };
-
-        if quantity > position.quantity {
-            return 0.0;
-        }
+        (realized_pnl, matched_quantity)
+    }
 
-        position.quantity -= quantity;
-        if position.quantity <= 0.0 {
-            self.positions.remove(symbol);
+    /// Reduce a position (a sell). `quantity` is always positive; selling more
+    /// than the current long closes it FIFO and opens a fresh short with the
+    /// remainder, realizing P&L per matched lot. Returns
+    /// `(released_cash, realized_pnl, matched_quantity)`.
+    pub fn reduce_position(
+        &mut self,
+        symbol: &str,
+        quantity: f64,
+        price: f64,
+        trade_date: chrono::NaiveDate,
+        init_margin: f64,
+        maint_margin: f64,
+    ) -> (f64, f64, f64) {
+        let lots = self.open_lots.entry(symbol.to_string()).or_insert_with(Vec::new);
+        let (realized_pnl, matched_quantity) = Self::match_fifo_lots(lots, quantity, price, false);
+        let remainder = quantity - matched_quantity;
+        if remainder > 0.0 {
+            lots.push(BuyRecord { date: trade_date, quantity: -remainder, price });
         }
+        self.realized_pnl += realized_pnl;
+        self.sync_position_from_lots(symbol, init_margin, maint_margin);
 
-        // Return released cash
-        quantity * price * 100.0
+        // Return released cash (sale proceeds, whether closing a long or opening a short)
+        let released_cash = quantity * price * 100.0;
+        (released_cash, realized_pnl, matched_quantity)
     }
 }
 
@@ -0,0 +1,526 @@
+// Expr: a qlib-style operator expression layer over OHLCV bars, so
+// strategies can request derived features ("Ref($close, 1)", "Mean($close,
+// 20)", "($high - $low) / $close") as strings instead of hand-coding rolling
+// loops in Python. A small recursive-descent parser turns each expression
+// into an `Expr` tree; `Expr::eval` then walks it against a symbol's aligned
+// bar series, computing rolling windows in O(n) via running sums/deques
+// rather than re-scanning each window from scratch.
+
+use crate::types::Bar;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum RollingOp {
+    Mean,
+    Std,
+    Sum,
+    Max,
+    Min,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Field(Field),
+    Const(f64),
+    Neg(Box<Expr>),
+    BinOp(BinOp, Box<Expr>, Box<Expr>),
+    /// Shift by `n` bars: `Ref(x, n)[i] = x[i - n]`, NaN while `i < n`.
+    Ref(Box<Expr>, usize),
+    /// Rolling window of width `n` ending at (and including) the current bar.
+    Rolling(RollingOp, Box<Expr>, usize),
+    /// Rolling Pearson correlation of `x` and `y` over a window of width `n`.
+    Corr(Box<Expr>, Box<Expr>, usize),
+}
+
+// ---- Tokenizer ----------------------------------------------------------
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Field(String),
+    LParen,
+    RParen,
+    Comma,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '$' {
+            let start = i + 1;
+            let mut j = start;
+            while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                j += 1;
+            }
+            if j == start {
+                return Err(format!("expected field name after '$' at position {}", i));
+            }
+            tokens.push(Token::Field(chars[start..j].iter().collect()));
+            i = j;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).map_or(false, |d| d.is_ascii_digit())) {
+            let start = i;
+            let mut j = i;
+            while j < chars.len() && (chars[j].is_ascii_digit() || chars[j] == '.') {
+                j += 1;
+            }
+            let text: String = chars[start..j].iter().collect();
+            let value: f64 = text
+                .parse()
+                .map_err(|_| format!("invalid number literal '{}'", text))?;
+            tokens.push(Token::Num(value));
+            i = j;
+        } else if c.is_alphabetic() {
+            let start = i;
+            let mut j = i;
+            while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                j += 1;
+            }
+            tokens.push(Token::Ident(chars[start..j].iter().collect()));
+            i = j;
+        } else {
+            let token = match c {
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                ',' => Token::Comma,
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '*' => Token::Star,
+                '/' => Token::Slash,
+                _ => return Err(format!("unexpected character '{}' at position {}", c, i)),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+// ---- Recursive-descent parser -------------------------------------------
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), String> {
+        match self.advance() {
+            Some(ref t) if t == token => Ok(()),
+            other => Err(format!("expected {:?}, found {:?}", token, other)),
+        }
+    }
+
+    /// `expr := term (('+' | '-') term)*`
+    fn parse_expr(&mut self) -> Result<Expr, String> {
+        let mut node = self.parse_term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.advance();
+                    node = Expr::BinOp(BinOp::Add, Box::new(node), Box::new(self.parse_term()?));
+                }
+                Some(Token::Minus) => {
+                    self.advance();
+                    node = Expr::BinOp(BinOp::Sub, Box::new(node), Box::new(self.parse_term()?));
+                }
+                _ => break,
+            }
+        }
+        Ok(node)
+    }
+
+    /// `term := unary (('*' | '/') unary)*`
+    fn parse_term(&mut self) -> Result<Expr, String> {
+        let mut node = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.advance();
+                    node = Expr::BinOp(BinOp::Mul, Box::new(node), Box::new(self.parse_unary()?));
+                }
+                Some(Token::Slash) => {
+                    self.advance();
+                    node = Expr::BinOp(BinOp::Div, Box::new(node), Box::new(self.parse_unary()?));
+                }
+                _ => break,
+            }
+        }
+        Ok(node)
+    }
+
+    /// `unary := '-' unary | primary`
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if matches!(self.peek(), Some(Token::Minus)) {
+            self.advance();
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    /// `primary := NUMBER | '$' FIELD | IDENT '(' args ')' | '(' expr ')'`
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.advance() {
+            Some(Token::Num(value)) => Ok(Expr::Const(value)),
+            Some(Token::Field(name)) => Ok(Expr::Field(parse_field(&name)?)),
+            Some(Token::LParen) => {
+                let node = self.parse_expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(node)
+            }
+            Some(Token::Ident(name)) => self.parse_call(&name),
+            other => Err(format!("expected an expression, found {:?}", other)),
+        }
+    }
+
+    fn parse_call(&mut self, name: &str) -> Result<Expr, String> {
+        self.expect(&Token::LParen)?;
+        let node = match name {
+            "Ref" => {
+                let x = self.parse_expr()?;
+                self.expect(&Token::Comma)?;
+                let n = self.parse_window()?;
+                Expr::Ref(Box::new(x), n)
+            }
+            "Mean" | "Std" | "Sum" | "Max" | "Min" => {
+                let op = match name {
+                    "Mean" => RollingOp::Mean,
+                    "Std" => RollingOp::Std,
+                    "Sum" => RollingOp::Sum,
+                    "Max" => RollingOp::Max,
+                    "Min" => RollingOp::Min,
+                    _ => unreachable!(),
+                };
+                let x = self.parse_expr()?;
+                self.expect(&Token::Comma)?;
+                let n = self.parse_window()?;
+                Expr::Rolling(op, Box::new(x), n)
+            }
+            "Corr" => {
+                let x = self.parse_expr()?;
+                self.expect(&Token::Comma)?;
+                let y = self.parse_expr()?;
+                self.expect(&Token::Comma)?;
+                let n = self.parse_window()?;
+                Expr::Corr(Box::new(x), Box::new(y), n)
+            }
+            other => return Err(format!("unknown operator '{}'", other)),
+        };
+        self.expect(&Token::RParen)?;
+        Ok(node)
+    }
+
+    /// A window-length argument: always an integer literal, never a
+    /// sub-expression (matches qlib's own operator surface).
+    fn parse_window(&mut self) -> Result<usize, String> {
+        match self.advance() {
+            Some(Token::Num(value)) if value >= 0.0 && value.fract() == 0.0 => Ok(value as usize),
+            other => Err(format!("expected an integer window size, found {:?}", other)),
+        }
+    }
+}
+
+fn parse_field(name: &str) -> Result<Field, String> {
+    match name {
+        "open" => Ok(Field::Open),
+        "high" => Ok(Field::High),
+        "low" => Ok(Field::Low),
+        "close" => Ok(Field::Close),
+        "volume" => Ok(Field::Volume),
+        other => Err(format!("unknown field '${}'", other)),
+    }
+}
+
+/// Parse a single qlib-style expression string into an `Expr` tree.
+fn parse(src: &str) -> Result<Expr, String> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(format!("unexpected trailing input in '{}'", src));
+    }
+    Ok(expr)
+}
+
+// ---- Evaluator -----------------------------------------------------------
+
+impl Expr {
+    /// Evaluate this expression over `bars`, returning one value per bar
+    /// (same length and order). `Ref` pads its first `n` entries with NaN;
+    /// rolling ops and `Corr` emit a value as soon as their window holds `n`
+    /// bars, so they pad only their first `n - 1` entries (or fewer, if the
+    /// whole series is shorter).
+    fn eval(&self, bars: &[Bar]) -> Vec<f64> {
+        match self {
+            Expr::Field(field) => bars.iter().map(|bar| field_value(bar, *field)).collect(),
+            Expr::Const(value) => vec![*value; bars.len()],
+            Expr::Neg(x) => x.eval(bars).into_iter().map(|v| -v).collect(),
+            Expr::BinOp(op, lhs, rhs) => {
+                let lhs = lhs.eval(bars);
+                let rhs = rhs.eval(bars);
+                lhs.into_iter().zip(rhs).map(|(l, r)| apply_binop(*op, l, r)).collect()
+            }
+            Expr::Ref(x, n) => shift(&x.eval(bars), *n),
+            Expr::Rolling(op, x, n) => rolling(*op, &x.eval(bars), *n),
+            Expr::Corr(x, y, n) => rolling_corr(&x.eval(bars), &y.eval(bars), *n),
+        }
+    }
+}
+
+fn field_value(bar: &Bar, field: Field) -> f64 {
+    match field {
+        Field::Open => bar.open,
+        Field::High => bar.high,
+        Field::Low => bar.low,
+        Field::Close => bar.close,
+        Field::Volume => bar.volume,
+    }
+}
+
+fn apply_binop(op: BinOp, lhs: f64, rhs: f64) -> f64 {
+    match op {
+        BinOp::Add => lhs + rhs,
+        BinOp::Sub => lhs - rhs,
+        BinOp::Mul => lhs * rhs,
+        BinOp::Div => lhs / rhs,
+    }
+}
+
+fn shift(values: &[f64], n: usize) -> Vec<f64> {
+    let mut out = Vec::with_capacity(values.len());
+    for i in 0..values.len() {
+        if i < n {
+            out.push(f64::NAN);
+        } else {
+            out.push(values[i - n]);
+        }
+    }
+    out
+}
+
+/// Rolling `Mean`/`Sum`/`Std` maintain a running sum (and sum of squares, for
+/// `Std`) over the trailing window, updating it by adding the incoming value
+/// and subtracting the one leaving the window rather than re-summing each
+/// window from scratch. A NaN entering the window (e.g. from a `Ref`/rolling
+/// op's own warm-up) is counted in `nan_count` instead of being folded into
+/// the sum, so it can leave the window again without permanently poisoning
+/// it; the output is NaN only while `nan_count > 0`. `Max`/`Min` instead keep
+/// a monotonic deque of (index, value) candidates, since a sliding window
+/// extremum can't be updated by a simple running total.
+fn rolling(op: RollingOp, values: &[f64], n: usize) -> Vec<f64> {
+    if n == 0 {
+        return vec![f64::NAN; values.len()];
+    }
+    match op {
+        RollingOp::Mean | RollingOp::Sum | RollingOp::Std => {
+            let mut out = Vec::with_capacity(values.len());
+            let mut sum = 0.0;
+            let mut sum_sq = 0.0;
+            let mut nan_count = 0usize;
+            for (i, &value) in values.iter().enumerate() {
+                if value.is_nan() {
+                    nan_count += 1;
+                } else {
+                    sum += value;
+                    sum_sq += value * value;
+                }
+                if i >= n {
+                    let dropped = values[i - n];
+                    if dropped.is_nan() {
+                        nan_count -= 1;
+                    } else {
+                        sum -= dropped;
+                        sum_sq -= dropped * dropped;
+                    }
+                }
+                if i + 1 < n || nan_count > 0 {
+                    out.push(f64::NAN);
+                    continue;
+                }
+                let window_n = n as f64;
+                out.push(match op {
+                    RollingOp::Sum => sum,
+                    RollingOp::Mean => sum / window_n,
+                    RollingOp::Std => {
+                        let mean = sum / window_n;
+                        (sum_sq / window_n - mean * mean).max(0.0).sqrt()
+                    }
+                    _ => unreachable!(),
+                });
+            }
+            out
+        }
+        RollingOp::Max => rolling_extremum(values, n, |a, b| a >= b),
+        RollingOp::Min => rolling_extremum(values, n, |a, b| a <= b),
+    }
+}
+
+/// Monotonic-deque sliding-window extremum: `keep(a, b)` decides whether
+/// candidate `a` should stay ahead of a newly-arrived `b` (`>=` for max,
+/// `<=` for min). O(n) total since each index enters and leaves the deque
+/// at most once.
+fn rolling_extremum(values: &[f64], n: usize, keep: impl Fn(f64, f64) -> bool) -> Vec<f64> {
+    let mut out = Vec::with_capacity(values.len());
+    let mut deque: VecDeque<usize> = VecDeque::new();
+    for i in 0..values.len() {
+        while let Some(&back) = deque.back() {
+            if keep(values[back], values[i]) {
+                break;
+            }
+            deque.pop_back();
+        }
+        deque.push_back(i);
+        if let Some(&front) = deque.front() {
+            if front + n <= i {
+                deque.pop_front();
+            }
+        }
+        if i + 1 < n {
+            out.push(f64::NAN);
+        } else {
+            out.push(values[*deque.front().unwrap()]);
+        }
+    }
+    out
+}
+
+/// Rolling Pearson correlation over a window of width `n`, via running sums
+/// of `x`, `y`, `x*y`, `x^2`, `y^2` updated incrementally as the window
+/// slides, same shape as the `Mean`/`Std` running sums above. A row where
+/// either `x` or `y` is NaN is excluded from the sums and counted in
+/// `nan_count` instead, so it can age out of the window again without
+/// poisoning every later correlation; the output is NaN only while
+/// `nan_count > 0`.
+fn rolling_corr(x: &[f64], y: &[f64], n: usize) -> Vec<f64> {
+    if n < 2 {
+        return vec![f64::NAN; x.len()];
+    }
+    let mut out = Vec::with_capacity(x.len());
+    let (mut sum_x, mut sum_y, mut sum_xy, mut sum_x2, mut sum_y2) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    let mut nan_count = 0usize;
+    for i in 0..x.len() {
+        if x[i].is_nan() || y[i].is_nan() {
+            nan_count += 1;
+        } else {
+            sum_x += x[i];
+            sum_y += y[i];
+            sum_xy += x[i] * y[i];
+            sum_x2 += x[i] * x[i];
+            sum_y2 += y[i] * y[i];
+        }
+        if i >= n {
+            let (dx, dy) = (x[i - n], y[i - n]);
+            if dx.is_nan() || dy.is_nan() {
+                nan_count -= 1;
+            } else {
+                sum_x -= dx;
+                sum_y -= dy;
+                sum_xy -= dx * dy;
+                sum_x2 -= dx * dx;
+                sum_y2 -= dy * dy;
+            }
+        }
+        if i + 1 < n || nan_count > 0 {
+            out.push(f64::NAN);
+            continue;
+        }
+        let window_n = n as f64;
+        let cov = sum_xy / window_n - (sum_x / window_n) * (sum_y / window_n);
+        let var_x = sum_x2 / window_n - (sum_x / window_n).powi(2);
+        let var_y = sum_y2 / window_n - (sum_y / window_n).powi(2);
+        let denom = (var_x * var_y).max(0.0).sqrt();
+        out.push(if denom == 0.0 { f64::NAN } else { cov / denom });
+    }
+    out
+}
+
+/// Parse and evaluate `expressions` over `symbol`'s bar series loaded from a
+/// qlib-style `dataset_dir` (see `qlib_store`), optionally resampled to
+/// `timeframe` first. Returns the shared datetime index (RFC3339, ascending)
+/// alongside one aligned column per expression, keyed by its original
+/// string — ready to hand straight to `pandas.DataFrame(columns, index=...)`
+/// or `numpy.array()` per column.
+#[pyfunction]
+#[pyo3(signature = (dataset_dir, symbol, expressions, timeframe=None, start=None, end=None))]
+pub fn compute_expressions(
+    dataset_dir: String,
+    symbol: String,
+    expressions: Vec<String>,
+    timeframe: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+) -> PyResult<(Vec<String>, HashMap<String, Vec<f64>>)> {
+    let parse_date = |s: &str| {
+        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))
+    };
+    let start = start.as_deref().map(parse_date).transpose()?;
+    let end = end.as_deref().map(parse_date).transpose()?;
+
+    let calendar = crate::qlib_store::load_calendar(std::path::Path::new(&dataset_dir))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    let instruments = crate::qlib_store::load_instruments(std::path::Path::new(&dataset_dir))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    let bars = crate::qlib_store::load_symbol_bars(
+        std::path::Path::new(&dataset_dir),
+        &symbol,
+        &calendar,
+        &instruments,
+        &[],
+        start,
+        end,
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+    let bars = match timeframe {
+        Some(period) => crate::datafeed::resample_bars(&bars, &period)
+            .map_err(|e| PyErr::new::<PyValueError, _>(e))?,
+        None => bars,
+    };
+
+    let index: Vec<String> = bars.iter().map(|bar| bar.datetime.to_rfc3339()).collect();
+    let mut columns = HashMap::with_capacity(expressions.len());
+    for source in expressions {
+        let expr = parse(&source).map_err(|e| PyErr::new::<PyValueError, _>(e))?;
+        columns.insert(source, expr.eval(&bars));
+    }
+    Ok((index, columns))
+}
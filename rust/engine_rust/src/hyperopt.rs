@@ -0,0 +1,580 @@
+// Hyperopt: Tree-structured Parzen Estimator search over BacktestEngine trials
+
+use crate::engine::PyBacktestEngine;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// One tunable dimension of the search space.
+#[derive(Clone, Debug)]
+enum ParamSpec {
+    Float { low: f64, high: f64 },
+    Int { low: i64, high: i64 },
+    Categorical { choices: Vec<String> },
+}
+
+/// A sampled value for one parameter, passed to `build_trial` as a Python dict.
+#[derive(Clone, Debug, PartialEq)]
+enum ParamValue {
+    Float(f64),
+    Int(i64),
+    Categorical(String),
+}
+
+/// Which `PerformanceStats` field (read back from `run_backtest`'s result
+/// dict) a trial is scored against. All losses are minimized internally;
+/// maximization objectives are negated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Objective {
+    Sharpe,
+    MaxReturn,
+    MinDrawdown,
+}
+
+impl Objective {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sharpe" => Some(Self::Sharpe),
+            "max_return" => Some(Self::MaxReturn),
+            "min_drawdown" => Some(Self::MinDrawdown),
+            _ => None,
+        }
+    }
+
+    fn loss_from_stats(self, stats: &Bound<'_, PyDict>) -> PyResult<f64> {
+        let field = match self {
+            Self::Sharpe => "sharpe_ratio",
+            Self::MaxReturn => "total_return",
+            Self::MinDrawdown => "max_drawdown",
+        };
+        let value: f64 = stats
+            .get_item(field)?
+            .ok_or_else(|| PyValueError::new_err(format!("stats is missing '{}'", field)))?
+            .extract()?;
+        Ok(match self {
+            Self::MinDrawdown => value,
+            _ => -value,
+        })
+    }
+}
+
+/// One completed trial: the sampled parameters and the resulting loss
+/// (lower is better).
+#[derive(Clone, Debug)]
+struct Trial {
+    params: HashMap<String, ParamValue>,
+    loss: f64,
+}
+
+/// Small deterministic xorshift64 generator so the sampler doesn't need an
+/// external RNG crate. Not cryptographic; only used to pick candidate draws.
+struct Xorshift64 {
+    state: u64,
+}
+
+impl Xorshift64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed | 1 }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal sample via Box-Muller.
+    fn gaussian(&mut self) -> f64 {
+        let u1 = self.next_f64().max(1e-12);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+fn gaussian_pdf(x: f64, mu: f64, sigma: f64) -> f64 {
+    let sigma = sigma.max(1e-9);
+    let z = (x - mu) / sigma;
+    (-0.5 * z * z).exp() / (sigma * (2.0 * std::f64::consts::PI).sqrt())
+}
+
+/// Silverman's rule of thumb, clamped so a tight or single-point group
+/// doesn't collapse to a degenerate kernel.
+fn bandwidth(values: &[f64], low: f64, high: f64) -> f64 {
+    let span = (high - low).abs().max(1e-9);
+    if values.len() < 2 {
+        return (span * 0.2).max(1e-6);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (variance.sqrt() * 1.06 * n.powf(-0.2)).max(span * 0.01).max(1e-6)
+}
+
+fn continuous_density(x: f64, values: &[f64], low: f64, high: f64) -> f64 {
+    if values.is_empty() {
+        return 1.0 / (high - low).abs().max(1e-9);
+    }
+    let bw = bandwidth(values, low, high);
+    values.iter().map(|&v| gaussian_pdf(x, v, bw)).sum::<f64>() / values.len() as f64
+}
+
+fn sample_continuous(values: &[f64], low: f64, high: f64, rng: &mut Xorshift64) -> f64 {
+    if values.is_empty() {
+        return low + rng.next_f64() * (high - low);
+    }
+    let bw = bandwidth(values, low, high);
+    let idx = ((rng.next_f64() * values.len() as f64) as usize).min(values.len() - 1);
+    (values[idx] + rng.gaussian() * bw).clamp(low.min(high), low.max(high))
+}
+
+/// Laplace-smoothed frequency of each choice within `values`.
+fn categorical_probs(values: &[&str], choices: &[String]) -> HashMap<String, f64> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for v in values {
+        *counts.entry(*v).or_insert(0) += 1;
+    }
+    let k = choices.len() as f64;
+    let n = values.len() as f64;
+    choices
+        .iter()
+        .map(|c| {
+            let count = *counts.get(c.as_str()).unwrap_or(&0) as f64;
+            (c.clone(), (count + 1.0) / (n + k))
+        })
+        .collect()
+}
+
+fn sample_categorical(probs: &HashMap<String, f64>, choices: &[String], rng: &mut Xorshift64) -> String {
+    let r = rng.next_f64();
+    let mut acc = 0.0;
+    for c in choices {
+        acc += probs[c];
+        if r <= acc {
+            return c.clone();
+        }
+    }
+    choices.last().expect("categorical param has no choices").clone()
+}
+
+fn uniform_sample(space: &HashMap<String, ParamSpec>, rng: &mut Xorshift64) -> HashMap<String, ParamValue> {
+    space
+        .iter()
+        .map(|(name, spec)| {
+            let value = match spec {
+                ParamSpec::Float { low, high } => ParamValue::Float(low + rng.next_f64() * (high - low)),
+                ParamSpec::Int { low, high } => {
+                    let span = (high - low + 1) as f64;
+                    ParamValue::Int(low + (rng.next_f64() * span) as i64)
+                }
+                ParamSpec::Categorical { choices } => {
+                    let idx = ((rng.next_f64() * choices.len() as f64) as usize).min(choices.len() - 1);
+                    ParamValue::Categorical(choices[idx].clone())
+                }
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+/// Split `trials` into a "good" (best `gamma` fraction by loss) and "bad"
+/// group, model each parameter's density in both (Gaussian kernels over
+/// continuous params, Laplace-smoothed frequencies over categoricals), and
+/// draw `n_candidates` full candidates from the good-group model, keeping
+/// whichever maximizes the good/bad density ratio `l(x)/g(x)` summed (in log
+/// space) across parameters.
+fn sample_next(
+    space: &HashMap<String, ParamSpec>,
+    trials: &[Trial],
+    gamma: f64,
+    n_candidates: usize,
+    rng: &mut Xorshift64,
+) -> HashMap<String, ParamValue> {
+    if trials.len() < 2 {
+        return uniform_sample(space, rng);
+    }
+
+    let mut sorted: Vec<&Trial> = trials.iter().collect();
+    sorted.sort_by(|a, b| a.loss.partial_cmp(&b.loss).unwrap_or(std::cmp::Ordering::Equal));
+
+    let n_good = (((sorted.len() as f64) * gamma).ceil() as usize).clamp(1, sorted.len() - 1);
+    let (good, bad) = sorted.split_at(n_good);
+
+    let mut best_params = None;
+    let mut best_log_ratio = f64::NEG_INFINITY;
+
+    for _ in 0..n_candidates.max(1) {
+        let mut candidate = HashMap::new();
+        let mut log_ratio = 0.0;
+
+        for (name, spec) in space {
+            match spec {
+                ParamSpec::Float { low, high } => {
+                    let good_values = float_values(good, name);
+                    let bad_values = float_values(bad, name);
+                    let x = sample_continuous(&good_values, *low, *high, rng);
+                    let l = continuous_density(x, &good_values, *low, *high);
+                    let g = continuous_density(x, &bad_values, *low, *high);
+                    log_ratio += (l.max(1e-300) / g.max(1e-300)).ln();
+                    candidate.insert(name.clone(), ParamValue::Float(x));
+                }
+                ParamSpec::Int { low, high } => {
+                    let (lo, hi) = (*low as f64, *high as f64);
+                    let good_values = int_values(good, name);
+                    let bad_values = int_values(bad, name);
+                    let x = sample_continuous(&good_values, lo, hi, rng).round();
+                    let l = continuous_density(x, &good_values, lo, hi);
+                    let g = continuous_density(x, &bad_values, lo, hi);
+                    log_ratio += (l.max(1e-300) / g.max(1e-300)).ln();
+                    candidate.insert(name.clone(), ParamValue::Int(x.clamp(lo, hi) as i64));
+                }
+                ParamSpec::Categorical { choices } => {
+                    let good_values = categorical_values(good, name);
+                    let bad_values = categorical_values(bad, name);
+                    let good_probs = categorical_probs(&good_values, choices);
+                    let bad_probs = categorical_probs(&bad_values, choices);
+                    let choice = sample_categorical(&good_probs, choices, rng);
+                    log_ratio += (good_probs[&choice] / bad_probs[&choice]).ln();
+                    candidate.insert(name.clone(), ParamValue::Categorical(choice));
+                }
+            }
+        }
+
+        if log_ratio > best_log_ratio {
+            best_log_ratio = log_ratio;
+            best_params = Some(candidate);
+        }
+    }
+
+    best_params.unwrap_or_else(|| uniform_sample(space, rng))
+}
+
+fn float_values(trials: &[&Trial], name: &str) -> Vec<f64> {
+    trials
+        .iter()
+        .filter_map(|t| match t.params.get(name) {
+            Some(ParamValue::Float(v)) => Some(*v),
+            _ => None,
+        })
+        .collect()
+}
+
+fn int_values(trials: &[&Trial], name: &str) -> Vec<f64> {
+    trials
+        .iter()
+        .filter_map(|t| match t.params.get(name) {
+            Some(ParamValue::Int(v)) => Some(*v as f64),
+            _ => None,
+        })
+        .collect()
+}
+
+fn categorical_values<'a>(trials: &'a [&Trial], name: &str) -> Vec<&'a str> {
+    trials
+        .iter()
+        .filter_map(|t| match t.params.get(name) {
+            Some(ParamValue::Categorical(v)) => Some(v.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_param_space(dict: &Bound<'_, PyDict>) -> PyResult<HashMap<String, ParamSpec>> {
+    let mut space = HashMap::new();
+    for (key, value) in dict.iter() {
+        let name: String = key.extract()?;
+        let value_dict = value
+            .downcast::<PyDict>()
+            .map_err(|_| PyValueError::new_err(format!("param '{}' must map to a dict", name)))?;
+        let type_name: String = value_dict
+            .get_item("type")?
+            .ok_or_else(|| PyValueError::new_err(format!("param '{}' is missing 'type'", name)))?
+            .extract()?;
+
+        let spec = match type_name.as_str() {
+            "float" => ParamSpec::Float {
+                low: get_field(value_dict, &name, "low")?,
+                high: get_field(value_dict, &name, "high")?,
+            },
+            "int" => ParamSpec::Int {
+                low: get_field(value_dict, &name, "low")?,
+                high: get_field(value_dict, &name, "high")?,
+            },
+            "categorical" => {
+                let choices: Vec<String> = value_dict
+                    .get_item("choices")?
+                    .ok_or_else(|| PyValueError::new_err(format!("param '{}' is missing 'choices'", name)))?
+                    .extract()?;
+                if choices.is_empty() {
+                    return Err(PyValueError::new_err(format!("param '{}' has no categorical choices", name)));
+                }
+                ParamSpec::Categorical { choices }
+            }
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "param '{}' has unknown type '{}', expected 'float', 'int', or 'categorical'",
+                    name, other
+                )))
+            }
+        };
+        space.insert(name, spec);
+    }
+
+    if space.is_empty() {
+        return Err(PyValueError::new_err("param_space must describe at least one parameter"));
+    }
+    Ok(space)
+}
+
+fn get_field<'py, T: FromPyObject<'py>>(dict: &Bound<'py, PyDict>, param: &str, field: &str) -> PyResult<T> {
+    dict.get_item(field)?
+        .ok_or_else(|| PyValueError::new_err(format!("param '{}' is missing '{}'", param, field)))?
+        .extract()
+}
+
+fn params_to_pydict<'py>(py: Python<'py>, params: &HashMap<String, ParamValue>) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    for (name, value) in params {
+        match value {
+            ParamValue::Float(v) => dict.set_item(name, v)?,
+            ParamValue::Int(v) => dict.set_item(name, v)?,
+            ParamValue::Categorical(v) => dict.set_item(name, v)?,
+        }
+    }
+    Ok(dict)
+}
+
+fn trial_to_pydict<'py>(py: Python<'py>, trial: &Trial) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("params", params_to_pydict(py, &trial.params)?)?;
+    dict.set_item("loss", trial.loss)?;
+    Ok(dict)
+}
+
+/// Runs a Tree-structured Parzen Estimator search over a `BacktestEngine`
+/// parameter space, similar to freqtrade's hyperopt.
+///
+/// `build_trial` is a Python callable `(params: dict) -> (engine, strategy,
+/// create_context)` producing a fresh, independent `BacktestEngine` (with its
+/// market data already loaded) plus the strategy/context-factory pair that
+/// `BacktestEngine.run_backtest` already expects, parameterized by the
+/// sampled `params`. Each trial calls `run_backtest` on its own engine and
+/// scores the result with `objective` (`"sharpe"`, `"max_return"`, or
+/// `"min_drawdown"`).
+///
+/// `n_threads` worker threads each claim trials and run them independently,
+/// but since `run_backtest` calls back into `strategy` (`on_start`/`on_bar`/
+/// `on_trade`) every bar, each trial holds the GIL for essentially its whole
+/// run — the Python global lock serializes trial execution across workers
+/// regardless of `n_threads`. What `n_threads` buys today is overlap between
+/// a trial's Python-calling bar loop and another worker's TPE sampling/
+/// bookkeeping (which run GIL-free), not concurrent simulation; it is not a
+/// multiplier on wall-clock throughput the way a CPU-bound, GIL-free search
+/// would be.
+#[pyclass]
+pub struct PyHyperoptRunner {
+    build_trial: Py<PyAny>,
+    space: HashMap<String, ParamSpec>,
+    objective: Objective,
+    n_trials: usize,
+    n_startup_trials: usize,
+    gamma: f64,
+    n_candidates: usize,
+    n_threads: usize,
+}
+
+#[pymethods]
+impl PyHyperoptRunner {
+    #[new]
+    #[pyo3(signature = (build_trial, param_space, objective, n_trials, n_startup_trials=None, gamma=None, n_candidates=None, n_threads=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        build_trial: Py<PyAny>,
+        param_space: &Bound<'_, PyDict>,
+        objective: String,
+        n_trials: usize,
+        n_startup_trials: Option<usize>,
+        gamma: Option<f64>,
+        n_candidates: Option<usize>,
+        n_threads: Option<usize>,
+    ) -> PyResult<Self> {
+        let space = parse_param_space(param_space)?;
+        let objective = Objective::parse(&objective).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown objective '{}', expected 'sharpe', 'max_return', or 'min_drawdown'",
+                objective
+            ))
+        })?;
+
+        Ok(Self {
+            build_trial,
+            n_startup_trials: n_startup_trials.unwrap_or_else(|| (space.len() * 2).max(10)),
+            space,
+            objective,
+            n_trials,
+            gamma: gamma.unwrap_or(0.15),
+            n_candidates: n_candidates.unwrap_or(24),
+            n_threads: n_threads.unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)),
+        })
+    }
+
+    /// Run every trial across `n_threads` worker threads (each engine is
+    /// independent), and return a dict with `"trials"` (ranked best-loss-
+    /// first), `"best_params"`, `"best_loss"`, and `"best_stats"`. See the
+    /// struct-level doc comment: trial execution itself still serializes on
+    /// the GIL, so this parallelizes scheduling/sampling rather than the
+    /// simulation loop.
+    fn run(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let trials: Mutex<Vec<Trial>> = Mutex::new(Vec::with_capacity(self.n_trials));
+        let best: Mutex<Option<(Trial, Py<PyAny>)>> = Mutex::new(None);
+        let first_error: Mutex<Option<PyErr>> = Mutex::new(None);
+        let next_index = AtomicUsize::new(0);
+
+        py.allow_threads(|| {
+            std::thread::scope(|scope| {
+                let next_index = &next_index;
+                let trials = &trials;
+                let best = &best;
+                let first_error = &first_error;
+                for worker in 0..self.n_threads.max(1) {
+                    scope.spawn(move || {
+                        self.run_worker(worker, next_index, trials, best, first_error)
+                    });
+                }
+            });
+        });
+
+        if let Some(err) = first_error.into_inner().unwrap() {
+            return Err(err);
+        }
+
+        let mut finished = trials.into_inner().unwrap();
+        finished.sort_by(|a, b| a.loss.partial_cmp(&b.loss).unwrap_or(std::cmp::Ordering::Equal));
+        let (best_trial, best_stats) = best
+            .into_inner()
+            .unwrap()
+            .ok_or_else(|| PyValueError::new_err("hyperopt produced no successful trials"))?;
+
+        let trial_list: Vec<PyObject> = finished
+            .iter()
+            .map(|t| trial_to_pydict(py, t).map(Into::into))
+            .collect::<PyResult<_>>()?;
+
+        let result = PyDict::new_bound(py);
+        result.set_item("trials", trial_list)?;
+        result.set_item("best_params", params_to_pydict(py, &best_trial.params)?)?;
+        result.set_item("best_loss", best_trial.loss)?;
+        result.set_item("best_stats", best_stats)?;
+        Ok(result.into())
+    }
+}
+
+impl PyHyperoptRunner {
+    /// One worker thread's claim loop: pulls the next trial index, samples
+    /// params from the TPE model fit on trials completed so far (tolerating
+    /// some staleness from trials still in flight on other threads), runs
+    /// it, and records the result.
+    fn run_worker(
+        &self,
+        worker: usize,
+        next_index: &AtomicUsize,
+        trials: &Mutex<Vec<Trial>>,
+        best: &Mutex<Option<(Trial, Py<PyAny>)>>,
+        first_error: &Mutex<Option<PyErr>>,
+    ) {
+        let mut rng = Xorshift64::new(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(1)
+                ^ (worker as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15),
+        );
+
+        loop {
+            if first_error.lock().unwrap().is_some() {
+                return;
+            }
+            let index = next_index.fetch_add(1, Ordering::SeqCst);
+            if index >= self.n_trials {
+                return;
+            }
+
+            let params = {
+                let completed = trials.lock().unwrap();
+                if completed.len() < self.n_startup_trials {
+                    uniform_sample(&self.space, &mut rng)
+                } else {
+                    sample_next(&self.space, &completed, self.gamma, self.n_candidates, &mut rng)
+                }
+            };
+
+            match Python::with_gil(|py| self.run_trial(py, &params)) {
+                Ok((loss, stats)) => {
+                    let trial = Trial { params, loss };
+                    {
+                        let mut best_guard = best.lock().unwrap();
+                        let is_best = best_guard.as_ref().map_or(true, |(b, _)| loss < b.loss);
+                        if is_best {
+                            *best_guard = Some((trial.clone(), stats));
+                        }
+                    }
+                    trials.lock().unwrap().push(trial);
+                }
+                Err(err) => {
+                    let mut slot = first_error.lock().unwrap();
+                    if slot.is_none() {
+                        *slot = Some(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Build and run a single trial's engine, returning its loss and the
+    /// `"stats"` dict produced by `run_backtest`. Called with the GIL held
+    /// for the whole trial (see the struct-level doc comment) since
+    /// `run_backtest` calls back into `strategy` every bar.
+    fn run_trial(&self, py: Python<'_>, params: &HashMap<String, ParamValue>) -> PyResult<(f64, Py<PyAny>)> {
+        let params_dict = params_to_pydict(py, params)?;
+        let built = self.build_trial.bind(py).call1((params_dict,))?;
+        let tuple = built.downcast::<PyTuple>().map_err(|_| {
+            PyValueError::new_err("build_trial(params) must return a (engine, strategy, create_context) tuple")
+        })?;
+        if tuple.len() != 3 {
+            return Err(PyValueError::new_err("build_trial(params) must return a 3-item tuple"));
+        }
+
+        let engine_obj = tuple.get_item(0)?;
+        let strategy_obj = tuple.get_item(1)?;
+        let create_context_obj = tuple.get_item(2)?;
+        let engine_bound = engine_obj
+            .downcast::<PyBacktestEngine>()
+            .map_err(|_| PyValueError::new_err("build_trial(params)[0] must be a BacktestEngine"))?;
+
+        let result = PyBacktestEngine::run_backtest(engine_bound, py, &strategy_obj, &create_context_obj, true)?;
+        let result_dict = result.bind(py).downcast::<PyDict>()?.clone();
+        let stats = result_dict
+            .get_item("stats")?
+            .ok_or_else(|| PyValueError::new_err("run_backtest result is missing 'stats'"))?;
+        let stats_dict = stats
+            .downcast::<PyDict>()
+            .map_err(|_| PyValueError::new_err("run_backtest result's 'stats' must be a dict"))?;
+
+        let loss = self.objective.loss_from_stats(stats_dict)?;
+        Ok((loss, stats.unbind()))
+    }
+}
+
+pub fn register_module(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyHyperoptRunner>()?;
+    Ok(())
+}
@@ -1,28 +1,103 @@
 // IndicatorEngine: Indicator registration and pre-computation
 
 use crate::datafeed::DataFeed;
+use crate::types::Bar;
+use pyo3::prelude::*;
 use std::collections::HashMap;
+use std::sync::Arc;
 use ta::indicators::SimpleMovingAverage;
 use ta::Next;
 
+/// A streaming indicator driven bar-by-bar, mirroring `ta::Next` so downstream
+/// crates can plug in EMA, MACD, ATR, etc. into `IndicatorDef::Custom` without
+/// touching this file: `compute_all_indicators` feeds every bar through `next`
+/// exactly like it drives `SimpleMovingAverage` for the built-in `"sma"` today.
+pub trait StreamingIndicator: Send {
+    /// Feed the next bar (in ascending timeline order) and return the
+    /// indicator's current value.
+    fn next(&mut self, bar: &Bar) -> f64;
+    /// Reset all internal state, e.g. before recomputing from the start of history.
+    fn reset(&mut self);
+    /// Leading bars required before the indicator is warmed up; values at a
+    /// narrower trailing window are masked as NaN, same as `"sma"`'s `period - 1`.
+    fn lookback(&self) -> usize;
+}
+
 /// Indicator definition
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum IndicatorDef {
     RustBuiltin {
         name: String,
         params: HashMap<String, String>,
         lookback_period: usize,
+        /// Higher timeframe (e.g. `"5m"`, `"1d"`) to resample the symbol's
+        /// base-resolution bars to before computing this indicator, or
+        /// `None` to compute directly on the base series.
+        timeframe: Option<String>,
     },
     PythonFunction {
         name: String,
+        /// The registered callable, invoked once per symbol as
+        /// `callback(close_values: list[float], lookback_period: int) ->
+        /// list[float]` during `compute_all_indicators` so pandas/NumPy
+        /// indicators get precomputed and timeline-aligned like the Rust
+        /// built-ins, instead of relying solely on per-bar `set_indicator_value`.
+        callback: Py<PyAny>,
         lookback_period: usize,
+        timeframe: Option<String>,
     },
+    /// A downstream-supplied `StreamingIndicator`, built fresh for each
+    /// symbol by `factory` so each gets its own independent state.
+    Custom {
+        factory: Arc<dyn Fn() -> Box<dyn StreamingIndicator> + Send + Sync>,
+        lookback_period: usize,
+        timeframe: Option<String>,
+    },
+}
+
+impl std::fmt::Debug for IndicatorDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndicatorDef::RustBuiltin { name, params, lookback_period, timeframe } => f
+                .debug_struct("RustBuiltin")
+                .field("name", name)
+                .field("params", params)
+                .field("lookback_period", lookback_period)
+                .field("timeframe", timeframe)
+                .finish(),
+            IndicatorDef::PythonFunction { name, lookback_period, timeframe, .. } => f
+                .debug_struct("PythonFunction")
+                .field("name", name)
+                .field("lookback_period", lookback_period)
+                .field("timeframe", timeframe)
+                .finish(),
+            IndicatorDef::Custom { lookback_period, timeframe, .. } => f
+                .debug_struct("Custom")
+                .field("lookback_period", lookback_period)
+                .field("timeframe", timeframe)
+                .finish(),
+        }
+    }
+}
+
+/// The default output name used for single-output indicators (e.g. `"sma"`),
+/// so `get_indicator_value`/`get_indicator_value_count` keep working without
+/// callers having to name an output.
+const DEFAULT_OUTPUT: &str = "value";
+
+/// Output names a built-in indicator emits, in the order `compute_all_indicators`
+/// fills them. Single-output indicators (the common case) just emit `DEFAULT_OUTPUT`.
+fn output_names(indicator_name: &str) -> &'static [&'static str] {
+    match indicator_name {
+        "bbands" => &["middle", "upper", "lower"],
+        _ => &[DEFAULT_OUTPUT],
+    }
 }
 
 /// Indicator engine
 pub struct IndicatorEngine {
     indicators: HashMap<String, IndicatorDef>,
-    indicator_values: HashMap<(String, String), Vec<f64>>, // (indicator_name, symbol) -> values
+    indicator_values: HashMap<(String, String, String), Vec<f64>>, // (indicator_name, symbol, output) -> values
     current_index: usize,
 }
 
@@ -55,23 +130,41 @@ impl IndicatorEngine {
         let timeline_len = timeline.len();
 
         for (indicator_name, def) in &self.indicators.clone() {
-            let lookback = match def {
-                IndicatorDef::RustBuiltin { lookback_period, .. } => *lookback_period,
-                IndicatorDef::PythonFunction { lookback_period, .. } => *lookback_period,
+            let (lookback, timeframe) = match def {
+                IndicatorDef::RustBuiltin { lookback_period, timeframe, .. } => (*lookback_period, timeframe),
+                IndicatorDef::PythonFunction { lookback_period, timeframe, .. } => (*lookback_period, timeframe),
+                IndicatorDef::Custom { lookback_period, timeframe, .. } => (*lookback_period, timeframe),
             };
 
             for symbol in &symbols {
-                let key = (indicator_name.clone(), symbol.clone());
+                let outputs = output_names(indicator_name);
+                let insert_nan_outputs = |engine: &mut Self| {
+                    for output in outputs {
+                        let nan_values = vec![f64::NAN; timeline_len];
+                        engine.indicator_values.insert(
+                            (indicator_name.clone(), symbol.clone(), output.to_string()),
+                            nan_values,
+                        );
+                    }
+                };
                 let mut values = Vec::with_capacity(timeline_len);
 
-                // Get all bars for this symbol
-                let all_bars = datafeed.get_all_bars_for_symbol(symbol);
+                // Get all bars for this symbol, resampled to the indicator's
+                // target timeframe if one was registered
+                let all_bars = match timeframe {
+                    Some(period) => match crate::datafeed::resample_bars(&datafeed.get_all_bars_for_symbol(symbol), period) {
+                        Ok(bars) => bars,
+                        Err(_) => {
+                            // Invalid timeframe string, fill with NaN like an unknown indicator
+                            insert_nan_outputs(self);
+                            continue;
+                        }
+                    },
+                    None => datafeed.get_all_bars_for_symbol(symbol),
+                };
                 if all_bars.is_empty() {
                     // No data for this symbol, fill with NaN
-                    for _i in 0..timeline_len {
-                        values.push(f64::NAN);
-                    }
-                    self.indicator_values.insert(key, values);
+                    insert_nan_outputs(self);
                     continue;
                 }
 
@@ -95,10 +188,7 @@ impl IndicatorEngine {
                                 "volume" => all_bars.iter().map(|b| b.volume).collect(),
                                 _ => {
                                     // Invalid field, fill with NaN
-                                    for _i in 0..timeline_len {
-                                        values.push(f64::NAN);
-                                    }
-                                    self.indicator_values.insert(key, values);
+                                    insert_nan_outputs(self);
                                     continue;
                                 }
                             };
@@ -135,29 +225,311 @@ impl IndicatorEngine {
                                     values.push(sma_value);
                                 }
                             }
+                            self.indicator_values.insert(
+                                (indicator_name.clone(), symbol.clone(), DEFAULT_OUTPUT.to_string()),
+                                values,
+                            );
+                        } else if name == "smm" {
+                            // Simple moving median: outlier-robust alternative to SMA.
+                            let period: usize = params
+                                .get("period")
+                                .and_then(|s| s.parse().ok())
+                                .unwrap_or_else(|| lookback.max(1).max(20));
+                            let field = params.get("field").map(|s| s.as_str()).unwrap_or("close");
+
+                            let field_values: Vec<f64> = match field {
+                                "close" => all_bars.iter().map(|b| b.close).collect(),
+                                "open" => all_bars.iter().map(|b| b.open).collect(),
+                                "high" => all_bars.iter().map(|b| b.high).collect(),
+                                "low" => all_bars.iter().map(|b| b.low).collect(),
+                                "volume" => all_bars.iter().map(|b| b.volume).collect(),
+                                _ => {
+                                    // Invalid field, fill with NaN
+                                    insert_nan_outputs(self);
+                                    continue;
+                                }
+                            };
+
+                            // Sorted window of the last `period` values, plus a ring
+                            // buffer of raw inputs so the oldest value's sorted
+                            // position can be found again (via binary search) once
+                            // it needs to be evicted.
+                            let mut window: Vec<f64> = Vec::with_capacity(period);
+                            let mut ring: std::collections::VecDeque<f64> = std::collections::VecDeque::with_capacity(period);
+                            let mut smm_values: Vec<f64> = Vec::with_capacity(field_values.len());
+                            for &x in &field_values {
+                                let insert_pos = window.partition_point(|&v| v < x);
+                                window.insert(insert_pos, x);
+                                ring.push_back(x);
+
+                                if ring.len() > period {
+                                    let old = ring.pop_front().unwrap();
+                                    let old_pos = window
+                                        .binary_search_by(|v| v.partial_cmp(&old).unwrap_or(std::cmp::Ordering::Equal))
+                                        .unwrap_or_else(|i| i);
+                                    window.remove(old_pos);
+                                }
+
+                                if ring.len() < period {
+                                    smm_values.push(f64::NAN);
+                                } else {
+                                    let n = window.len();
+                                    let median = if n % 2 == 1 {
+                                        window[n / 2]
+                                    } else {
+                                        (window[n / 2 - 1] + window[n / 2]) / 2.0
+                                    };
+                                    smm_values.push(median);
+                                }
+                            }
+
+                            // For each timeline point, find the corresponding median value
+                            let mut bar_idx = 0; // Track position in all_bars for efficiency
+                            for i in 0..timeline_len {
+                                let current_time = timeline[i];
+
+                                // Advance bar_idx to find the last bar <= current_time
+                                while bar_idx < all_bars.len() && all_bars[bar_idx].datetime <= current_time {
+                                    bar_idx += 1;
+                                }
+
+                                if bar_idx == 0 {
+                                    values.push(f64::NAN);
+                                } else {
+                                    values.push(smm_values[bar_idx - 1]);
+                                }
+                            }
+                            self.indicator_values.insert(
+                                (indicator_name.clone(), symbol.clone(), DEFAULT_OUTPUT.to_string()),
+                                values,
+                            );
+                        } else if name == "rsi" {
+                            let period: usize = params
+                                .get("period")
+                                .and_then(|s| s.parse().ok())
+                                .unwrap_or_else(|| lookback.max(1).max(14));
+                            let field = params.get("field").map(|s| s.as_str()).unwrap_or("close");
+
+                            let field_values: Vec<f64> = match field {
+                                "close" => all_bars.iter().map(|b| b.close).collect(),
+                                "open" => all_bars.iter().map(|b| b.open).collect(),
+                                "high" => all_bars.iter().map(|b| b.high).collect(),
+                                "low" => all_bars.iter().map(|b| b.low).collect(),
+                                "volume" => all_bars.iter().map(|b| b.volume).collect(),
+                                _ => {
+                                    // Invalid field, fill with NaN
+                                    insert_nan_outputs(self);
+                                    continue;
+                                }
+                            };
+
+                            let rsi_values = crate::indicators::vectorized_rsi(&field_values, period);
+
+                            // For each timeline point, find the corresponding RSI value
+                            let mut bar_idx = 0; // Track position in all_bars for efficiency
+                            for i in 0..timeline_len {
+                                let current_time = timeline[i];
+
+                                // Advance bar_idx to find the last bar <= current_time
+                                while bar_idx < all_bars.len() && all_bars[bar_idx].datetime <= current_time {
+                                    bar_idx += 1;
+                                }
+
+                                if bar_idx == 0 {
+                                    values.push(f64::NAN);
+                                } else {
+                                    values.push(rsi_values[bar_idx - 1]);
+                                }
+                            }
+                            self.indicator_values.insert(
+                                (indicator_name.clone(), symbol.clone(), DEFAULT_OUTPUT.to_string()),
+                                values,
+                            );
+                        } else if name == "bbands" {
+                            // Bollinger Bands: middle = SMA(n), upper/lower = middle +/- k * population stddev.
+                            let period: usize = params
+                                .get("period")
+                                .and_then(|s| s.parse().ok())
+                                .unwrap_or_else(|| lookback.max(1).max(20));
+                            let k: f64 = params.get("k").and_then(|s| s.parse().ok()).unwrap_or(2.0);
+                            let field = params.get("field").map(|s| s.as_str()).unwrap_or("close");
+
+                            let field_values: Vec<f64> = match field {
+                                "close" => all_bars.iter().map(|b| b.close).collect(),
+                                "open" => all_bars.iter().map(|b| b.open).collect(),
+                                "high" => all_bars.iter().map(|b| b.high).collect(),
+                                "low" => all_bars.iter().map(|b| b.low).collect(),
+                                "volume" => all_bars.iter().map(|b| b.volume).collect(),
+                                _ => {
+                                    // Invalid field, fill with NaN
+                                    insert_nan_outputs(self);
+                                    continue;
+                                }
+                            };
+
+                            // Rolling sum / sum-of-squares over the trailing `period`
+                            // values gives each window's mean and population stddev in O(1).
+                            // A NaN entering the window (gappy/reindexed data) is counted in
+                            // `nan_count` instead of folded into the sums, so it can age out
+                            // again without permanently NaN-ing every later window.
+                            let mut middle_values: Vec<f64> = Vec::with_capacity(field_values.len());
+                            let mut upper_values: Vec<f64> = Vec::with_capacity(field_values.len());
+                            let mut lower_values: Vec<f64> = Vec::with_capacity(field_values.len());
+                            let mut sum = 0.0;
+                            let mut sum_sq = 0.0;
+                            let mut nan_count = 0usize;
+                            for (i, &x) in field_values.iter().enumerate() {
+                                if x.is_nan() {
+                                    nan_count += 1;
+                                } else {
+                                    sum += x;
+                                    sum_sq += x * x;
+                                }
+                                if i >= period {
+                                    let old = field_values[i - period];
+                                    if old.is_nan() {
+                                        nan_count -= 1;
+                                    } else {
+                                        sum -= old;
+                                        sum_sq -= old * old;
+                                    }
+                                }
+                                if i + 1 < period || nan_count > 0 {
+                                    middle_values.push(f64::NAN);
+                                    upper_values.push(f64::NAN);
+                                    lower_values.push(f64::NAN);
+                                } else {
+                                    let n = period as f64;
+                                    let mean = sum / n;
+                                    let variance = (sum_sq / n - mean * mean).max(0.0);
+                                    let sd = variance.sqrt();
+                                    middle_values.push(mean);
+                                    upper_values.push(mean + k * sd);
+                                    lower_values.push(mean - k * sd);
+                                }
+                            }
+
+                            // For each timeline point, find the corresponding window's values
+                            let mut bar_idx = 0; // Track position in all_bars for efficiency
+                            let mut middle_out = Vec::with_capacity(timeline_len);
+                            let mut upper_out = Vec::with_capacity(timeline_len);
+                            let mut lower_out = Vec::with_capacity(timeline_len);
+                            for i in 0..timeline_len {
+                                let current_time = timeline[i];
+
+                                // Advance bar_idx to find the last bar <= current_time
+                                while bar_idx < all_bars.len() && all_bars[bar_idx].datetime <= current_time {
+                                    bar_idx += 1;
+                                }
+
+                                if bar_idx == 0 || bar_idx - 1 < period - 1 {
+                                    middle_out.push(f64::NAN);
+                                    upper_out.push(f64::NAN);
+                                    lower_out.push(f64::NAN);
+                                } else {
+                                    middle_out.push(middle_values[bar_idx - 1]);
+                                    upper_out.push(upper_values[bar_idx - 1]);
+                                    lower_out.push(lower_values[bar_idx - 1]);
+                                }
+                            }
+
+                            self.indicator_values.insert((indicator_name.clone(), symbol.clone(), "middle".to_string()), middle_out);
+                            self.indicator_values.insert((indicator_name.clone(), symbol.clone(), "upper".to_string()), upper_out);
+                            self.indicator_values.insert((indicator_name.clone(), symbol.clone(), "lower".to_string()), lower_out);
                         } else {
                             // Unknown indicator, fill with NaN
-                            for _i in 0..timeline_len {
-                                values.push(f64::NAN);
+                            insert_nan_outputs(self);
+                        }
+                    }
+                    IndicatorDef::PythonFunction { callback, lookback_period, .. } => {
+                        // Vectorized callout: hand the whole close series to Python
+                        // once per symbol instead of churning per-bar `next()`
+                        // calls, mirroring how the Rust built-ins precompute above.
+                        let close_values: Vec<f64> = all_bars.iter().map(|b| b.close).collect();
+                        let python_values: PyResult<Vec<f64>> = Python::with_gil(|py| {
+                            callback
+                                .bind(py)
+                                .call1((close_values.clone(), *lookback_period))?
+                                .extract::<Vec<f64>>()
+                        });
+
+                        match python_values {
+                            Ok(py_values) if py_values.len() == all_bars.len() => {
+                                // For each timeline point, find the corresponding value
+                                let mut bar_idx = 0; // Track position in all_bars for efficiency
+                                for i in 0..timeline_len {
+                                    let current_time = timeline[i];
+
+                                    // Advance bar_idx to find the last bar <= current_time
+                                    while bar_idx < all_bars.len() && all_bars[bar_idx].datetime <= current_time {
+                                        bar_idx += 1;
+                                    }
+
+                                    if bar_idx == 0 {
+                                        values.push(f64::NAN);
+                                    } else {
+                                        values.push(py_values[bar_idx - 1]);
+                                    }
+                                }
+                                self.indicator_values.insert(
+                                    (indicator_name.clone(), symbol.clone(), DEFAULT_OUTPUT.to_string()),
+                                    values,
+                                );
                             }
+                            // Callback raised or returned a mismatched length: fall
+                            // back to the old per-bar mode, where `set_indicator_value`
+                            // fills in values one at a time after this NaN baseline.
+                            _ => insert_nan_outputs(self),
                         }
                     }
-                    IndicatorDef::PythonFunction { .. } => {
-                        // Python functions are computed on-demand, fill with NaN for now
-                        for _i in 0..timeline_len {
-                            values.push(f64::NAN);
+                    IndicatorDef::Custom { factory, .. } => {
+                        let mut indicator = factory();
+                        indicator.reset();
+                        let warmup = indicator.lookback();
+
+                        // Pre-compute the indicator's value at every bar
+                        let mut indicator_values: Vec<f64> = Vec::with_capacity(all_bars.len());
+                        for bar in &all_bars {
+                            indicator_values.push(indicator.next(bar));
+                        }
+
+                        // For each timeline point, find the corresponding value
+                        let mut bar_idx = 0; // Track position in all_bars for efficiency
+                        for i in 0..timeline_len {
+                            let current_time = timeline[i];
+
+                            // Advance bar_idx to find the last bar <= current_time
+                            while bar_idx < all_bars.len() && all_bars[bar_idx].datetime <= current_time {
+                                bar_idx += 1;
+                            }
+
+                            if bar_idx == 0 || bar_idx - 1 < warmup.saturating_sub(1) {
+                                values.push(f64::NAN);
+                            } else {
+                                values.push(indicator_values[bar_idx - 1]);
+                            }
                         }
+                        self.indicator_values.insert(
+                            (indicator_name.clone(), symbol.clone(), DEFAULT_OUTPUT.to_string()),
+                            values,
+                        );
                     }
                 }
-
-                self.indicator_values.insert(key, values);
             }
         }
     }
 
-    /// Get indicator value for current bar
+    /// Get indicator value for current bar, for the default/first output —
+    /// kept for single-output indicators like `"sma"`. Multi-output
+    /// indicators (e.g. `"bbands"`) need `get_indicator_value_named`.
     pub fn get_indicator_value(&self, name: &str, symbol: &str) -> Option<f64> {
-        let key = (name.to_string(), symbol.to_string());
+        self.get_indicator_value_named(name, symbol, DEFAULT_OUTPUT)
+    }
+
+    /// Get indicator value for current bar, for a specific named output (e.g.
+    /// `"middle"`/`"upper"`/`"lower"` for `"bbands"`).
+    pub fn get_indicator_value_named(&self, name: &str, symbol: &str, output: &str) -> Option<f64> {
+        let key = (name.to_string(), symbol.to_string(), output.to_string());
         let values = self.indicator_values.get(&key)?;
 
         if self.current_index >= values.len() {
@@ -173,9 +545,10 @@ impl IndicatorEngine {
         }
     }
 
-    /// Get indicator values for past N bars (including current)
+    /// Get indicator values for past N bars (including current), for the
+    /// default/first output.
     pub fn get_indicator_value_count(&self, name: &str, symbol: &str, count: usize) -> Option<Vec<f64>> {
-        let key = (name.to_string(), symbol.to_string());
+        let key = (name.to_string(), symbol.to_string(), DEFAULT_OUTPUT.to_string());
         let values = self.indicator_values.get(&key)?;
 
         if self.current_index >= values.len() {
@@ -215,9 +588,10 @@ impl IndicatorEngine {
         self.current_index = index;
     }
 
-    /// Set indicator value (for Python-computed indicators)
+    /// Set indicator value (for Python-computed indicators), for the
+    /// default/first output.
     pub fn set_indicator_value(&mut self, name: &str, symbol: &str, index: usize, value: f64) {
-        let key = (name.to_string(), symbol.to_string());
+        let key = (name.to_string(), symbol.to_string(), DEFAULT_OUTPUT.to_string());
         if let Some(values) = self.indicator_values.get_mut(&key) {
             if index < values.len() {
                 values[index] = value;
@@ -4,25 +4,80 @@ mod database;
 mod datafeed;
 mod engine;
 mod execution_engine;
+mod expr;
+mod hyperopt;
 mod indicator_engine;
 mod metrics_recorder;
+mod qlib_store;
 mod types;
+mod universe;
 
 pub use database::{get_market_data, resample_klines, save_klines, save_klines_from_csv};
+pub use expr::compute_expressions;
 pub use engine::{PyBacktestConfig, PyBacktestEngine, PyBar};
+pub use hyperopt::PyHyperoptRunner;
 
-// Placeholder for indicators module - can be added later
 pub mod indicators {
-    pub fn vectorized_sma(_data: &[f64], _period: usize) -> Vec<f64> {
-        vec![]
+    /// Simple moving average over `period`-sized trailing windows, using a
+    /// running sum for O(1) per-window updates. NaN for indices `< period - 1`.
+    pub fn vectorized_sma(data: &[f64], period: usize) -> Vec<f64> {
+        let mut result = Vec::with_capacity(data.len());
+        let mut sum = 0.0;
+        for (i, &x) in data.iter().enumerate() {
+            sum += x;
+            if i >= period {
+                sum -= data[i - period];
+            }
+            if i + 1 < period {
+                result.push(f64::NAN);
+            } else {
+                result.push(sum / period as f64);
+            }
+        }
+        result
     }
-    
-    pub fn vectorized_rsi(_data: &[f64], _period: usize) -> Vec<f64> {
-        vec![]
+
+    /// Wilder's RSI over `period`-sized windows: the first average gain/loss
+    /// is seeded as the simple mean of the first `period` gains/losses, then
+    /// smoothed recursively thereafter. NaN for indices `< period` (no prior
+    /// delta exists for index 0, so the first `period` deltas span indices
+    /// `1..=period`).
+    pub fn vectorized_rsi(data: &[f64], period: usize) -> Vec<f64> {
+        let mut result = vec![f64::NAN; data.len()];
+        if data.len() <= period || period == 0 {
+            return result;
+        }
+
+        let gains: Vec<f64> = (1..data.len()).map(|i| (data[i] - data[i - 1]).max(0.0)).collect();
+        let losses: Vec<f64> = (1..data.len()).map(|i| (-(data[i] - data[i - 1])).max(0.0)).collect();
+
+        let mut avg_gain = gains[..period].iter().sum::<f64>() / period as f64;
+        let mut avg_loss = losses[..period].iter().sum::<f64>() / period as f64;
+        result[period] = rsi_from_averages(avg_gain, avg_loss);
+
+        for i in period..gains.len() {
+            avg_gain = (avg_gain * (period - 1) as f64 + gains[i]) / period as f64;
+            avg_loss = (avg_loss * (period - 1) as f64 + losses[i]) / period as f64;
+            // `gains`/`losses` are delta-indexed (entry `i` is the delta between
+            // `data[i]` and `data[i+1]`), so this average lands on `data[i + 1]`.
+            result[i + 1] = rsi_from_averages(avg_gain, avg_loss);
+        }
+
+        result
+    }
+
+    fn rsi_from_averages(avg_gain: f64, avg_loss: f64) -> f64 {
+        if avg_loss == 0.0 {
+            100.0
+        } else {
+            let rs = avg_gain / avg_loss;
+            100.0 - 100.0 / (1.0 + rs)
+        }
     }
 }
 
 #[pymodule]
 fn engine_rust(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
-    engine::register_module(py, m)
-} 
\ No newline at end of file
+    engine::register_module(py, m)?;
+    hyperopt::register_module(py, m)
+}
\ No newline at end of file
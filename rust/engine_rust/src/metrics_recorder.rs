@@ -1,45 +1,119 @@
 // MetricsRecorder: Performance metrics recording and calculation
 
-use crate::types::{EquityPoint, Fill, PerformanceStats};
+use crate::types::{EquityPoint, Fill, MetricsConfig, Period, PeriodStats, PerformanceStats, RoundTrip};
 use chrono::{DateTime, Utc};
 
 pub struct MetricsRecorder {
     equity_curve: Vec<EquityPoint>,
     fills: Vec<Fill>,
     benchmark_curve: Vec<EquityPoint>,
+    /// When set, recording methods update running accumulators instead of
+    /// `equity_curve`/`fills`/`benchmark_curve`, which stay empty. See
+    /// `new_streaming`.
+    streaming: Option<Box<StreamingState>>,
+    config: MetricsConfig,
+    /// Running total of perpetual-futures funding charged against cash, kept
+    /// outside `streaming` since it's already an O(1) accumulator in both modes.
+    total_funding_cost: f64,
 }
 
 impl MetricsRecorder {
     pub fn new() -> Self {
+        Self::with_config(MetricsConfig::default())
+    }
+
+    /// Like `new`, but with a `MetricsConfig` other than the daily-equities
+    /// default (e.g. a non-zero risk-free rate, or `periods_per_year: 365`
+    /// for crypto).
+    pub fn with_config(config: MetricsConfig) -> Self {
+        Self {
+            equity_curve: Vec::new(),
+            fills: Vec::new(),
+            benchmark_curve: Vec::new(),
+            streaming: None,
+            config,
+            total_funding_cost: 0.0,
+        }
+    }
+
+    /// Opt-in streaming mode for multi-year minute-bar runs: maintains
+    /// Welford-style running mean/variance, peak-tracking drawdown, and
+    /// incremental trade P&L instead of retaining the full equity/fill
+    /// history, so memory stays bounded regardless of run length.
+    /// `calculate_stats` matches the buffered path within floating-point
+    /// tolerance; the trade-off is that `calculate_periodic_stats` and the
+    /// equity-curve/fills getters have nothing to report, since the curves
+    /// themselves aren't retained.
+    pub fn new_streaming() -> Self {
+        Self::streaming_with_config(MetricsConfig::default())
+    }
+
+    /// Like `new_streaming`, but with a `MetricsConfig` other than the
+    /// daily-equities default.
+    pub fn streaming_with_config(config: MetricsConfig) -> Self {
         Self {
             equity_curve: Vec::new(),
             fills: Vec::new(),
             benchmark_curve: Vec::new(),
+            streaming: Some(Box::new(StreamingState::new(config))),
+            config,
+            total_funding_cost: 0.0,
         }
     }
 
     /// Record equity point
     pub fn record_equity(&mut self, datetime: DateTime<Utc>, equity: f64) {
-        self.equity_curve.push(EquityPoint { datetime, equity });
+        if let Some(state) = &mut self.streaming {
+            state.record_equity(datetime, equity);
+        } else {
+            self.equity_curve.push(EquityPoint { datetime, equity });
+        }
     }
 
     /// Record benchmark equity point
     pub fn record_benchmark(&mut self, datetime: DateTime<Utc>, equity: f64) {
-        self.benchmark_curve.push(EquityPoint { datetime, equity });
+        if let Some(state) = &mut self.streaming {
+            state.record_benchmark(datetime, equity);
+        } else {
+            self.benchmark_curve.push(EquityPoint { datetime, equity });
+        }
     }
 
     /// Record fill
     pub fn record_fill(&mut self, fill: Fill) {
-        self.fills.push(fill);
+        if let Some(state) = &mut self.streaming {
+            state.record_fill(&fill);
+        } else {
+            self.fills.push(fill);
+        }
     }
 
     /// Record multiple fills
     pub fn record_fills(&mut self, fills: Vec<Fill>) {
-        self.fills.extend(fills);
+        if self.streaming.is_some() {
+            for fill in fills {
+                self.record_fill(fill);
+            }
+        } else {
+            self.fills.extend(fills);
+        }
+    }
+
+    /// Record a perpetual-futures funding charge against cash (positive =
+    /// net paid out this event, negative = net received).
+    pub fn record_funding(&mut self, amount: f64) {
+        self.total_funding_cost += amount;
     }
 
     /// Calculate performance statistics
     pub fn calculate_stats(&self) -> PerformanceStats {
+        if let Some(state) = &self.streaming {
+            let mut stats = state.calculate_stats();
+            stats.total_funding_cost = self.total_funding_cost;
+            stats.funding_pnl = -self.total_funding_cost;
+            return stats;
+        }
+
         let strategy_stats = if self.equity_curve.is_empty() {
             PerformanceStats {
                 total_return: 0.0,
@@ -57,6 +131,26 @@ impl MetricsRecorder {
                 benchmark_max_drawdown: None,
                 benchmark_max_drawdown_start: None,
                 benchmark_max_drawdown_end: None,
+                sortino_ratio: 0.0,
+                profit_factor: 0.0,
+                expectancy: 0.0,
+                beta: None,
+                alpha: None,
+                tracking_error: None,
+                information_ratio: None,
+                avg_holding_period_days: 0.0,
+                largest_win: 0.0,
+                largest_loss: 0.0,
+                max_win_streak: 0,
+                max_loss_streak: 0,
+                avg_win: 0.0,
+                avg_loss: 0.0,
+                calmar_ratio: 0.0,
+                trading_pnl: 0.0,
+                funding_pnl: -self.total_funding_cost,
+                total_funding_cost: self.total_funding_cost,
+                total_commission: 0.0,
+                total_slippage: 0.0,
             }
         } else {
             let initial_equity = self.equity_curve[0].equity;
@@ -71,7 +165,7 @@ impl MetricsRecorder {
             } else {
                 1.0
             };
-            let years = days / 365.25;
+            let years = days / self.config.calendar_days_per_year;
             let annualized_return = if years > 0.0 {
                 (final_equity / initial_equity).powf(1.0 / years) - 1.0
             } else {
@@ -84,8 +178,16 @@ impl MetricsRecorder {
             // Calculate Sharpe ratio
             let sharpe_ratio = self.calculate_sharpe_ratio();
 
-            // Calculate win rate and profit/loss ratio
-            let (win_rate, profit_loss_ratio) = self.calculate_trade_stats();
+            // Build the FIFO round-trip ledger and derive trade statistics from it
+            let round_trips = self.build_round_trips();
+            let (win_rate, profit_loss_ratio, profit_factor, expectancy, avg_win, avg_loss) =
+                self.calculate_trade_stats(&round_trips);
+            let (avg_holding_period_days, largest_win, largest_loss, max_win_streak, max_loss_streak) =
+                Self::calculate_round_trip_aggregates(&round_trips);
+
+            // Calculate Sortino ratio
+            let sortino_ratio =
+                self.calculate_sortino_ratio(self.config.risk_free_rate / self.config.periods_per_year);
 
             // Count open and close trades
             let open_count = self.fills.iter()
@@ -96,9 +198,22 @@ impl MetricsRecorder {
                 .count();
 
             // Calculate benchmark statistics
-            let (benchmark_return, benchmark_annualized_return, benchmark_max_dd, benchmark_max_dd_start, benchmark_max_dd_end) = 
+            let (benchmark_return, benchmark_annualized_return, benchmark_max_dd, benchmark_max_dd_start, benchmark_max_dd_end) =
                 self.calculate_benchmark_stats();
 
+            // Calculate benchmark-relative risk metrics (beta, alpha, tracking error, information ratio)
+            let (beta, alpha, tracking_error, information_ratio) = self.calculate_benchmark_relative_stats();
+
+            // Trading P&L is the sum of realized P&L across FIFO round trips;
+            // funding is tracked separately via `record_funding` so the two
+            // never overlap.
+            let trading_pnl: f64 = round_trips.iter().map(|rt| rt.realized_pnl).sum();
+
+            let total_commission: f64 = self.fills.iter().map(|f| f.commission).sum();
+            let total_slippage: f64 = self.fills.iter().map(|f| f.slippage).sum();
+
+            let calmar_ratio = Self::calculate_calmar_ratio(annualized_return, max_drawdown);
+
             PerformanceStats {
                 total_return,
                 annualized_return,
@@ -115,6 +230,26 @@ impl MetricsRecorder {
                 benchmark_max_drawdown: benchmark_max_dd,
                 benchmark_max_drawdown_start: benchmark_max_dd_start,
                 benchmark_max_drawdown_end: benchmark_max_dd_end,
+                sortino_ratio,
+                profit_factor,
+                expectancy,
+                beta,
+                alpha,
+                tracking_error,
+                information_ratio,
+                avg_holding_period_days,
+                largest_win,
+                largest_loss,
+                max_win_streak,
+                max_loss_streak,
+                avg_win,
+                avg_loss,
+                calmar_ratio,
+                trading_pnl,
+                funding_pnl: -self.total_funding_cost,
+                total_funding_cost: self.total_funding_cost,
+                total_commission,
+                total_slippage,
             }
         };
 
@@ -139,7 +274,7 @@ impl MetricsRecorder {
         } else {
             1.0
         };
-        let years = days / 365.25;
+        let years = days / self.config.calendar_days_per_year;
         let annualized_return = if years > 0.0 {
             (final_equity / initial_equity).powf(1.0 / years) - 1.0
         } else {
@@ -152,20 +287,111 @@ impl MetricsRecorder {
         (Some(total_return), Some(annualized_return), Some(max_drawdown), max_dd_start, max_dd_end)
     }
 
+    /// Calculate beta, annualized alpha, tracking error, and information ratio
+    /// from aligned daily returns of the strategy vs. the benchmark. Returns
+    /// all `None` when there's no benchmark curve or it isn't aligned in
+    /// length with the equity curve.
+    fn calculate_benchmark_relative_stats(&self) -> (Option<f64>, Option<f64>, Option<f64>, Option<f64>) {
+        if self.benchmark_curve.is_empty()
+            || self.benchmark_curve.len() != self.equity_curve.len()
+            || self.equity_curve.len() < 2
+        {
+            return (None, None, None, None);
+        }
+
+        let mut strategy_returns = Vec::new();
+        let mut benchmark_returns = Vec::new();
+        for i in 1..self.equity_curve.len() {
+            let prev_strategy = self.equity_curve[i - 1].equity;
+            let curr_strategy = self.equity_curve[i].equity;
+            let prev_benchmark = self.benchmark_curve[i - 1].equity;
+            let curr_benchmark = self.benchmark_curve[i].equity;
+            if prev_strategy > 0.0 && prev_benchmark > 0.0 {
+                strategy_returns.push((curr_strategy - prev_strategy) / prev_strategy);
+                benchmark_returns.push((curr_benchmark - prev_benchmark) / prev_benchmark);
+            }
+        }
+
+        if strategy_returns.len() < 2 {
+            return (None, None, None, None);
+        }
+
+        let n = strategy_returns.len() as f64;
+        let mean_strategy = strategy_returns.iter().sum::<f64>() / n;
+        let mean_benchmark = benchmark_returns.iter().sum::<f64>() / n;
+
+        let covariance = strategy_returns
+            .iter()
+            .zip(&benchmark_returns)
+            .map(|(s, b)| (s - mean_strategy) * (b - mean_benchmark))
+            .sum::<f64>()
+            / n;
+        let benchmark_variance = benchmark_returns
+            .iter()
+            .map(|b| (b - mean_benchmark).powi(2))
+            .sum::<f64>()
+            / n;
+
+        let beta = if benchmark_variance == 0.0 {
+            None
+        } else {
+            Some(covariance / benchmark_variance)
+        };
+        let alpha = beta.map(|beta| (mean_strategy - beta * mean_benchmark) * 252.0);
+
+        let excess_returns: Vec<f64> = strategy_returns
+            .iter()
+            .zip(&benchmark_returns)
+            .map(|(s, b)| s - b)
+            .collect();
+        let mean_excess = excess_returns.iter().sum::<f64>() / n;
+        let std_excess = (excess_returns
+            .iter()
+            .map(|e| (e - mean_excess).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+
+        let tracking_error = Some(std_excess * (252.0_f64).sqrt());
+        let information_ratio = if std_excess == 0.0 {
+            None
+        } else {
+            Some(mean_excess / std_excess * (252.0_f64).sqrt())
+        };
+
+        (beta, alpha, tracking_error, information_ratio)
+    }
+
     /// Calculate benchmark maximum drawdown with period information
     fn calculate_benchmark_max_drawdown_with_period(&self) -> (f64, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
-        if self.benchmark_curve.is_empty() {
+        Self::max_drawdown_with_period_over(&self.benchmark_curve)
+    }
+
+    /// Calculate maximum drawdown
+    fn calculate_max_drawdown(&self) -> f64 {
+        Self::max_drawdown_with_period_over(&self.equity_curve).0
+    }
+
+    /// Calculate maximum drawdown with period information
+    fn calculate_max_drawdown_with_period(&self) -> (f64, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        Self::max_drawdown_with_period_over(&self.equity_curve)
+    }
+
+    /// Peak-tracking max drawdown loop shared by the strategy, benchmark, and
+    /// per-period drawdown calculations.
+    fn max_drawdown_with_period_over(points: &[EquityPoint]) -> (f64, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        if points.is_empty() {
             return (0.0, None, None);
         }
 
-        let mut max_equity = self.benchmark_curve[0].equity;
-        let mut max_equity_time = self.benchmark_curve[0].datetime;
+        let mut max_equity = points[0].equity;
+        let mut max_equity_time = points[0].datetime;
         let mut max_dd = 0.0;
         let mut max_dd_start: Option<DateTime<Utc>> = None;
         let mut max_dd_end: Option<DateTime<Utc>> = None;
         let mut current_dd_start: Option<DateTime<Utc>> = None;
 
-        for point in &self.benchmark_curve {
+        for point in points {
             if point.equity > max_equity {
                 // New peak reached, reset drawdown tracking
                 max_equity = point.equity;
@@ -177,7 +403,7 @@ impl MetricsRecorder {
                     // Start of a new drawdown period
                     current_dd_start = Some(max_equity_time);
                 }
-                
+
                 let drawdown = (max_equity - point.equity) / max_equity;
                 if drawdown > max_dd {
                     max_dd = drawdown;
@@ -190,73 +416,85 @@ impl MetricsRecorder {
         (max_dd, max_dd_start, max_dd_end)
     }
 
-    /// Calculate maximum drawdown
-    fn calculate_max_drawdown(&self) -> f64 {
-        if self.equity_curve.is_empty() {
-            return 0.0;
+    /// Bucket `equity_curve` (and `benchmark_curve`, when present) into yearly
+    /// or monthly windows and summarize each window's return, drawdown, and
+    /// benchmark-relative excess return. Empty buckets are skipped. Always
+    /// empty in streaming mode, since the full curve isn't retained.
+    pub fn calculate_periodic_stats(&self, period: Period) -> Vec<PeriodStats> {
+        if self.streaming.is_some() {
+            return Vec::new();
         }
 
-        let mut max_equity = self.equity_curve[0].equity;
-        let mut max_dd = 0.0;
+        let benchmark_buckets = Self::bucket_by_period(&self.benchmark_curve, period);
 
-        for point in &self.equity_curve {
-            if point.equity > max_equity {
-                max_equity = point.equity;
-            }
-            let drawdown = (max_equity - point.equity) / max_equity;
-            if drawdown > max_dd {
-                max_dd = drawdown;
-            }
-        }
+        Self::bucket_by_period(&self.equity_curve, period)
+            .into_iter()
+            .map(|(label, points)| {
+                let first_equity = points[0].equity;
+                let last_equity = points.last().unwrap().equity;
+                let return_pct = if first_equity != 0.0 {
+                    last_equity / first_equity - 1.0
+                } else {
+                    0.0
+                };
+                let (max_drawdown, _, _) = Self::max_drawdown_with_period_over(&points);
+
+                let benchmark_return = benchmark_buckets
+                    .iter()
+                    .find(|(bucket_label, _)| *bucket_label == label)
+                    .and_then(|(_, bpoints)| {
+                        let bfirst = bpoints.first()?.equity;
+                        let blast = bpoints.last()?.equity;
+                        if bfirst != 0.0 {
+                            Some(blast / bfirst - 1.0)
+                        } else {
+                            None
+                        }
+                    });
+                let excess_return = benchmark_return.map(|benchmark| return_pct - benchmark);
 
-        max_dd
+                PeriodStats {
+                    label,
+                    return_pct,
+                    max_drawdown,
+                    benchmark_return,
+                    excess_return,
+                }
+            })
+            .collect()
     }
 
-    /// Calculate maximum drawdown with period information
-    fn calculate_max_drawdown_with_period(&self) -> (f64, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
-        if self.equity_curve.is_empty() {
-            return (0.0, None, None);
-        }
+    /// Group equity points into contiguous buckets sharing the same calendar
+    /// label (`"2024"` or `"2024-03"`), assuming `points` is already sorted by
+    /// time. Never produces an empty bucket.
+    fn bucket_by_period(points: &[EquityPoint], period: Period) -> Vec<(String, Vec<EquityPoint>)> {
+        let mut buckets: Vec<(String, Vec<EquityPoint>)> = Vec::new();
 
-        let mut max_equity = self.equity_curve[0].equity;
-        let mut max_equity_time = self.equity_curve[0].datetime;
-        let mut max_dd = 0.0;
-        let mut max_dd_start: Option<DateTime<Utc>> = None;
-        let mut max_dd_end: Option<DateTime<Utc>> = None;
-        let mut current_dd_start: Option<DateTime<Utc>> = None;
+        for point in points {
+            let label = match period {
+                Period::Yearly => point.datetime.format("%Y").to_string(),
+                Period::Monthly => point.datetime.format("%Y-%m").to_string(),
+            };
 
-        for point in &self.equity_curve {
-            if point.equity > max_equity {
-                // New peak reached, reset drawdown tracking
-                max_equity = point.equity;
-                max_equity_time = point.datetime;
-                current_dd_start = None;
-            } else {
-                // In drawdown
-                if current_dd_start.is_none() {
-                    // Start of a new drawdown period
-                    current_dd_start = Some(max_equity_time);
-                }
-                
-                let drawdown = (max_equity - point.equity) / max_equity;
-                if drawdown > max_dd {
-                    max_dd = drawdown;
-                    max_dd_start = current_dd_start;
-                    max_dd_end = Some(point.datetime);
+            match buckets.last_mut() {
+                Some((last_label, last_points)) if *last_label == label => {
+                    last_points.push(point.clone());
                 }
+                _ => buckets.push((label, vec![point.clone()])),
             }
         }
 
-        (max_dd, max_dd_start, max_dd_end)
+        buckets
     }
 
-    /// Calculate Sharpe ratio
+    /// Calculate Sharpe ratio, using `self.config.risk_free_rate` (converted to
+    /// a per-period rate via `periods_per_year`) as the excess-return baseline.
     fn calculate_sharpe_ratio(&self) -> f64 {
         if self.equity_curve.len() < 2 {
             return 0.0;
         }
 
-        // Calculate daily returns
+        // Calculate per-period returns
         let mut returns = Vec::new();
         for i in 1..self.equity_curve.len() {
             let prev_equity = self.equity_curve[i - 1].equity;
@@ -270,6 +508,7 @@ impl MetricsRecorder {
             return 0.0;
         }
 
+        let per_period_rf = self.config.risk_free_rate / self.config.periods_per_year;
         let mean_return = returns.iter().sum::<f64>() / returns.len() as f64;
         let variance = returns
             .iter()
@@ -282,55 +521,177 @@ impl MetricsRecorder {
             return 0.0;
         }
 
-        // Annualized Sharpe (assuming 252 trading days)
-        (mean_return / std_dev) * (252.0_f64).sqrt()
+        ((mean_return - per_period_rf) / std_dev) * self.config.periods_per_year.sqrt()
     }
 
-    /// Calculate trade statistics
-    fn calculate_trade_stats(&self) -> (f64, f64) {
-        // Group fills by round trips (simplified)
-        let mut profits = Vec::new();
-        let mut losses = Vec::new();
+    /// Calculate Sortino ratio: like `calculate_sharpe_ratio` but penalizes only
+    /// downside deviation below `target` instead of total volatility. Callers
+    /// pass the per-period risk-free rate (`risk_free_rate / periods_per_year`)
+    /// as `target` to match `calculate_sharpe_ratio`'s baseline.
+    fn calculate_sortino_ratio(&self, target: f64) -> f64 {
+        if self.equity_curve.len() < 2 {
+            return 0.0;
+        }
+
+        let mut returns = Vec::new();
+        for i in 1..self.equity_curve.len() {
+            let prev_equity = self.equity_curve[i - 1].equity;
+            let curr_equity = self.equity_curve[i].equity;
+            if prev_equity > 0.0 {
+                returns.push((curr_equity - prev_equity) / prev_equity);
+            }
+        }
 
-        // Simple approach: track buy/sell pairs
-        let mut positions: std::collections::HashMap<String, Vec<(f64, f64)>> =
+        if returns.is_empty() {
+            return 0.0;
+        }
+
+        let mean_return = returns.iter().sum::<f64>() / returns.len() as f64;
+        let downside_variance = returns
+            .iter()
+            .map(|r| (r - target).min(0.0).powi(2))
+            .sum::<f64>()
+            / returns.len() as f64;
+        let downside_dev = downside_variance.sqrt();
+
+        if downside_dev == 0.0 {
+            return 0.0;
+        }
+
+        (mean_return - target) / downside_dev * self.config.periods_per_year.sqrt()
+    }
+
+    /// FIFO-match buy and sell fills per symbol into a `RoundTrip` ledger,
+    /// carrying each open lot's entry price and datetime through the queue.
+    /// One fill draining several lots produces one `RoundTrip` per lot. A
+    /// symbol's queue only ever holds lots of one side at a time: a fill
+    /// first closes out opposite-side lots (a sell against long lots, a buy
+    /// against short lots) and, once those are exhausted, any leftover
+    /// quantity opens a new lot on the fill's own side — so a sell with no
+    /// open long position opens a short lot instead of being dropped.
+    fn build_round_trips(&self) -> Vec<RoundTrip> {
+        let mut round_trips = Vec::new();
+
+        // Open lots per symbol, FIFO-ordered: (quantity, entry price, entry
+        // time, entry commission per lot unit, is_long)
+        let mut positions: std::collections::HashMap<String, Vec<(f64, f64, DateTime<Utc>, f64, bool)>> =
             std::collections::HashMap::new();
 
         for fill in &self.fills {
             let entry = positions.entry(fill.symbol.clone()).or_insert_with(Vec::new);
+            let is_buy = matches!(fill.side, crate::types::OrderSide::Buy);
+            let commission_per_unit = if fill.quantity > 0.0 {
+                fill.commission / fill.quantity
+            } else {
+                0.0
+            };
 
-            match fill.side {
-                crate::types::OrderSide::Buy => {
-                    entry.push((fill.quantity, fill.price));
-                }
-                crate::types::OrderSide::Sell => {
-                    let mut remaining = fill.quantity;
-                    let mut total_cost = 0.0;
-
-                    while remaining > 0.0 && !entry.is_empty() {
-                        let (qty, price) = entry[0];
-                        let used = remaining.min(qty);
-                        total_cost += used * price * 100.0;
-                        remaining -= used;
-
-                        if used >= qty {
-                            entry.remove(0);
-                        } else {
-                            entry[0] = (qty - used, price);
-                        }
-                    }
+            let mut remaining = fill.quantity;
 
-                    if total_cost > 0.0 {
-                        let revenue = fill.quantity * fill.price * 100.0;
-                        let pnl = revenue - total_cost;
-                        if pnl > 0.0 {
-                            profits.push(pnl);
-                        } else {
-                            losses.push(pnl.abs());
-                        }
+            while remaining > 0.0 && !entry.is_empty() && entry[0].4 != is_buy {
+                let (qty, entry_price, entry_time, entry_commission_per_unit, is_long) = entry[0];
+                let used = remaining.min(qty);
+                let realized_pnl = if is_long {
+                    used * (fill.price - entry_price) * 100.0
+                } else {
+                    used * (entry_price - fill.price) * 100.0
+                };
+                let return_pct = if entry_price > 0.0 {
+                    if is_long {
+                        (fill.price - entry_price) / entry_price
+                    } else {
+                        (entry_price - fill.price) / entry_price
                     }
+                } else {
+                    0.0
+                };
+
+                round_trips.push(RoundTrip {
+                    symbol: fill.symbol.clone(),
+                    side: if is_long { "long".to_string() } else { "short".to_string() },
+                    entry_time,
+                    entry_price,
+                    exit_time: fill.timestamp,
+                    exit_price: fill.price,
+                    quantity: used,
+                    realized_pnl,
+                    return_pct,
+                    holding_period: fill.timestamp - entry_time,
+                    commission: used * (entry_commission_per_unit + commission_per_unit),
+                });
+
+                remaining -= used;
+                if used >= qty {
+                    entry.remove(0);
+                } else {
+                    entry[0] = (qty - used, entry_price, entry_time, entry_commission_per_unit, is_long);
                 }
             }
+
+            if remaining > 0.0 {
+                entry.push((remaining, fill.price, fill.timestamp, commission_per_unit, is_buy));
+            }
+        }
+
+        round_trips
+    }
+
+    /// Average holding period, largest win/loss, and longest win/loss streaks
+    /// across a round-trip ledger, walked in exit-time order.
+    fn calculate_round_trip_aggregates(round_trips: &[RoundTrip]) -> (f64, f64, f64, usize, usize) {
+        if round_trips.is_empty() {
+            return (0.0, 0.0, 0.0, 0, 0);
+        }
+
+        let total_days: f64 = round_trips
+            .iter()
+            .map(|rt| rt.holding_period.num_seconds() as f64 / 86400.0)
+            .sum();
+        let avg_holding_period_days = total_days / round_trips.len() as f64;
+
+        let largest_win = round_trips
+            .iter()
+            .map(|rt| rt.realized_pnl)
+            .fold(0.0, f64::max);
+        let largest_loss = round_trips
+            .iter()
+            .map(|rt| rt.realized_pnl)
+            .fold(0.0, f64::min)
+            .abs();
+
+        let mut by_exit_time: Vec<&RoundTrip> = round_trips.iter().collect();
+        by_exit_time.sort_by_key(|rt| rt.exit_time);
+
+        let mut max_win_streak = 0usize;
+        let mut max_loss_streak = 0usize;
+        let mut current_win_streak = 0usize;
+        let mut current_loss_streak = 0usize;
+        for rt in by_exit_time {
+            if rt.realized_pnl > 0.0 {
+                current_win_streak += 1;
+                current_loss_streak = 0;
+            } else {
+                current_loss_streak += 1;
+                current_win_streak = 0;
+            }
+            max_win_streak = max_win_streak.max(current_win_streak);
+            max_loss_streak = max_loss_streak.max(current_loss_streak);
+        }
+
+        (avg_holding_period_days, largest_win, largest_loss, max_win_streak, max_loss_streak)
+    }
+
+    /// Calculate trade statistics: win rate, profit/loss ratio, profit factor,
+    /// expectancy, and average win/loss, all derived from the round-trip ledger.
+    fn calculate_trade_stats(&self, round_trips: &[RoundTrip]) -> (f64, f64, f64, f64, f64, f64) {
+        let mut profits = Vec::new();
+        let mut losses = Vec::new();
+        for rt in round_trips {
+            if rt.realized_pnl > 0.0 {
+                profits.push(rt.realized_pnl);
+            } else {
+                losses.push(rt.realized_pnl.abs());
+            }
         }
 
         let total_trades = profits.len() + losses.len();
@@ -360,7 +721,31 @@ impl MetricsRecorder {
             0.0
         };
 
-        (win_rate, profit_loss_ratio)
+        let gross_profit: f64 = profits.iter().sum();
+        let gross_loss: f64 = losses.iter().sum();
+        let profit_factor = if gross_loss > 0.0 {
+            gross_profit / gross_loss
+        } else if gross_profit > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+
+        let expectancy = win_rate * avg_profit - (1.0 - win_rate) * avg_loss;
+
+        (win_rate, profit_loss_ratio, profit_factor, expectancy, avg_profit, avg_loss)
+    }
+
+    /// Annualized return over max drawdown magnitude; `f64::INFINITY` when
+    /// there was no drawdown and a positive return, 0 when both are zero.
+    fn calculate_calmar_ratio(annualized_return: f64, max_drawdown: f64) -> f64 {
+        if max_drawdown > 0.0 {
+            annualized_return / max_drawdown
+        } else if annualized_return > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        }
     }
 
     /// Get equity curve
@@ -373,9 +758,491 @@ impl MetricsRecorder {
         &self.fills
     }
 
+    /// Get the FIFO round-trip trade ledger
+    pub fn get_round_trips(&self) -> Vec<RoundTrip> {
+        self.build_round_trips()
+    }
+
     /// Get benchmark curve
     pub fn get_benchmark_curve(&self) -> &[EquityPoint] {
         &self.benchmark_curve
     }
 }
 
+/// Running mean/variance of a scalar series via Welford's online algorithm.
+/// `variance()` is the population variance (divides by `count`), matching
+/// the buffered calculations elsewhere in this file.
+struct WelfordStats {
+    count: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl WelfordStats {
+    fn new() -> Self {
+        Self { count: 0, mean: 0.0, m2: 0.0 }
+    }
+
+    fn update(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        let delta2 = x - self.mean;
+        self.m2 += delta * delta2;
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.m2 / self.count as f64
+        }
+    }
+}
+
+/// Running mean/variance/covariance of a paired series via the bivariate
+/// form of Welford's algorithm, so beta/alpha/tracking-error/information
+/// ratio can all be derived without retaining the underlying return vectors.
+struct BivariateWelford {
+    count: usize,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    c_xy: f64,
+}
+
+impl BivariateWelford {
+    fn new() -> Self {
+        Self { count: 0, mean_x: 0.0, mean_y: 0.0, m2_x: 0.0, m2_y: 0.0, c_xy: 0.0 }
+    }
+
+    fn update(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        let n = self.count as f64;
+        let dx = x - self.mean_x;
+        self.mean_x += dx / n;
+        let dy = y - self.mean_y;
+        self.mean_y += dy / n;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.c_xy += dx * (y - self.mean_y);
+    }
+
+    fn var_x(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.m2_x / self.count as f64 }
+    }
+
+    fn var_y(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.m2_y / self.count as f64 }
+    }
+
+    fn covariance(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.c_xy / self.count as f64 }
+    }
+}
+
+/// Peak-tracking max-drawdown accumulator: the streaming equivalent of
+/// `MetricsRecorder::max_drawdown_with_period_over`, updated one point at a
+/// time instead of scanning a retained curve.
+struct DrawdownTracker {
+    peak_equity: f64,
+    peak_time: Option<DateTime<Utc>>,
+    max_dd: f64,
+    max_dd_start: Option<DateTime<Utc>>,
+    max_dd_end: Option<DateTime<Utc>>,
+    current_dd_start: Option<DateTime<Utc>>,
+}
+
+impl DrawdownTracker {
+    fn new() -> Self {
+        Self {
+            peak_equity: 0.0,
+            peak_time: None,
+            max_dd: 0.0,
+            max_dd_start: None,
+            max_dd_end: None,
+            current_dd_start: None,
+        }
+    }
+
+    fn update(&mut self, equity: f64, time: DateTime<Utc>) {
+        if self.peak_time.is_none() {
+            self.peak_equity = equity;
+            self.peak_time = Some(time);
+            return;
+        }
+
+        if equity > self.peak_equity {
+            self.peak_equity = equity;
+            self.peak_time = Some(time);
+            self.current_dd_start = None;
+        } else {
+            if self.current_dd_start.is_none() {
+                self.current_dd_start = self.peak_time;
+            }
+
+            let drawdown = (self.peak_equity - equity) / self.peak_equity;
+            if drawdown > self.max_dd {
+                self.max_dd = drawdown;
+                self.max_dd_start = self.current_dd_start;
+                self.max_dd_end = Some(time);
+            }
+        }
+    }
+
+    fn result(&self) -> (f64, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        (self.max_dd, self.max_dd_start, self.max_dd_end)
+    }
+}
+
+/// Running accumulators backing `MetricsRecorder::new_streaming`. Open lots
+/// are kept per-symbol (bounded by currently-open positions, not by history)
+/// so trade stats and the round-trip aggregates can still be derived
+/// incrementally as sells drain them FIFO.
+struct StreamingState {
+    config: MetricsConfig,
+    first_equity: Option<(DateTime<Utc>, f64)>,
+    last_equity: Option<(DateTime<Utc>, f64)>,
+    prev_equity: Option<f64>,
+    equity_returns: WelfordStats,
+    downside_sq_sum: f64,
+    downside_count: usize,
+    equity_drawdown: DrawdownTracker,
+    /// The strategy return computed by the most recent `record_equity` call,
+    /// consumed by the next `record_benchmark` call to keep the two return
+    /// series aligned the way the buffered path aligns by index.
+    pending_strategy_return: Option<f64>,
+
+    first_benchmark: Option<(DateTime<Utc>, f64)>,
+    last_benchmark: Option<(DateTime<Utc>, f64)>,
+    prev_benchmark: Option<f64>,
+    benchmark_drawdown: DrawdownTracker,
+    benchmark_relative: BivariateWelford,
+
+    open_lots: std::collections::HashMap<String, Vec<(f64, f64, DateTime<Utc>, bool)>>,
+    profit_sum: f64,
+    profit_count: usize,
+    loss_sum: f64,
+    loss_count: usize,
+    total_holding_days: f64,
+    round_trip_count: usize,
+    largest_win: f64,
+    largest_loss: f64,
+    current_win_streak: usize,
+    current_loss_streak: usize,
+    max_win_streak: usize,
+    max_loss_streak: usize,
+    open_count: usize,
+    close_count: usize,
+    total_commission: f64,
+    total_slippage: f64,
+}
+
+impl StreamingState {
+    fn new(config: MetricsConfig) -> Self {
+        Self {
+            config,
+            first_equity: None,
+            last_equity: None,
+            prev_equity: None,
+            equity_returns: WelfordStats::new(),
+            downside_sq_sum: 0.0,
+            downside_count: 0,
+            equity_drawdown: DrawdownTracker::new(),
+            pending_strategy_return: None,
+            first_benchmark: None,
+            last_benchmark: None,
+            prev_benchmark: None,
+            benchmark_drawdown: DrawdownTracker::new(),
+            benchmark_relative: BivariateWelford::new(),
+            open_lots: std::collections::HashMap::new(),
+            profit_sum: 0.0,
+            profit_count: 0,
+            loss_sum: 0.0,
+            loss_count: 0,
+            total_holding_days: 0.0,
+            round_trip_count: 0,
+            largest_win: 0.0,
+            largest_loss: 0.0,
+            current_win_streak: 0,
+            current_loss_streak: 0,
+            max_win_streak: 0,
+            max_loss_streak: 0,
+            open_count: 0,
+            close_count: 0,
+            total_commission: 0.0,
+            total_slippage: 0.0,
+        }
+    }
+
+    fn record_equity(&mut self, datetime: DateTime<Utc>, equity: f64) {
+        if self.first_equity.is_none() {
+            self.first_equity = Some((datetime, equity));
+        }
+        self.last_equity = Some((datetime, equity));
+
+        if let Some(prev) = self.prev_equity {
+            if prev > 0.0 {
+                let r = (equity - prev) / prev;
+                self.equity_returns.update(r);
+                let per_period_rf = self.config.risk_free_rate / self.config.periods_per_year;
+                let downside = (r - per_period_rf).min(0.0);
+                self.downside_sq_sum += downside * downside;
+                self.downside_count += 1;
+                self.pending_strategy_return = Some(r);
+            }
+        }
+        self.prev_equity = Some(equity);
+
+        self.equity_drawdown.update(equity, datetime);
+    }
+
+    fn record_benchmark(&mut self, datetime: DateTime<Utc>, equity: f64) {
+        if self.first_benchmark.is_none() {
+            self.first_benchmark = Some((datetime, equity));
+        }
+        self.last_benchmark = Some((datetime, equity));
+
+        if let Some(prev) = self.prev_benchmark {
+            if prev > 0.0 {
+                let r = (equity - prev) / prev;
+                if let Some(strategy_r) = self.pending_strategy_return.take() {
+                    self.benchmark_relative.update(strategy_r, r);
+                }
+            }
+        }
+        self.prev_benchmark = Some(equity);
+
+        self.benchmark_drawdown.update(equity, datetime);
+    }
+
+    fn record_fill(&mut self, fill: &Fill) {
+        self.total_commission += fill.commission;
+        self.total_slippage += fill.slippage;
+
+        let entry = self
+            .open_lots
+            .entry(fill.symbol.clone())
+            .or_insert_with(Vec::new);
+        let is_buy = matches!(fill.side, crate::types::OrderSide::Buy);
+
+        if is_buy {
+            self.open_count += 1;
+        } else {
+            self.close_count += 1;
+        }
+
+        // Close opposite-side lots first (a sell against long lots, a buy
+        // against short lots); any leftover quantity opens a new lot on
+        // this fill's own side, so a sell with no open long position opens
+        // a short lot instead of being dropped.
+        let mut remaining = fill.quantity;
+
+        while remaining > 0.0 && !entry.is_empty() && entry[0].3 != is_buy {
+            let (qty, entry_price, entry_time, is_long) = entry[0];
+            let used = remaining.min(qty);
+            let pnl = if is_long {
+                used * (fill.price - entry_price) * 100.0
+            } else {
+                used * (entry_price - fill.price) * 100.0
+            };
+            let holding_days = (fill.timestamp - entry_time).num_seconds() as f64 / 86400.0;
+
+            if pnl > 0.0 {
+                self.profit_sum += pnl;
+                self.profit_count += 1;
+                self.largest_win = self.largest_win.max(pnl);
+                self.current_win_streak += 1;
+                self.current_loss_streak = 0;
+            } else {
+                self.loss_sum += pnl.abs();
+                self.loss_count += 1;
+                self.largest_loss = self.largest_loss.max(pnl.abs());
+                self.current_loss_streak += 1;
+                self.current_win_streak = 0;
+            }
+            self.max_win_streak = self.max_win_streak.max(self.current_win_streak);
+            self.max_loss_streak = self.max_loss_streak.max(self.current_loss_streak);
+
+            self.total_holding_days += holding_days;
+            self.round_trip_count += 1;
+
+            remaining -= used;
+            if used >= qty {
+                entry.remove(0);
+            } else {
+                entry[0] = (qty - used, entry_price, entry_time, is_long);
+            }
+        }
+
+        if remaining > 0.0 {
+            entry.push((remaining, fill.price, fill.timestamp, is_buy));
+        }
+    }
+
+    fn calculate_stats(&self) -> PerformanceStats {
+        let (total_return, annualized_return) = match (self.first_equity, self.last_equity) {
+            (Some((t0, e0)), Some((t1, e1))) if e0 != 0.0 => {
+                let total_return = (e1 - e0) / e0;
+                let days = if t1 > t0 { (t1 - t0).num_days() as f64 } else { 1.0 };
+                let years = days / self.config.calendar_days_per_year;
+                let annualized_return = if years > 0.0 {
+                    (e1 / e0).powf(1.0 / years) - 1.0
+                } else {
+                    total_return
+                };
+                (total_return, annualized_return)
+            }
+            _ => (0.0, 0.0),
+        };
+
+        let (max_drawdown, max_dd_start, max_dd_end) = self.equity_drawdown.result();
+
+        let per_period_rf = self.config.risk_free_rate / self.config.periods_per_year;
+
+        let sharpe_ratio = {
+            let std_dev = self.equity_returns.variance().sqrt();
+            if std_dev == 0.0 {
+                0.0
+            } else {
+                ((self.equity_returns.mean - per_period_rf) / std_dev) * self.config.periods_per_year.sqrt()
+            }
+        };
+
+        let sortino_ratio = if self.downside_count == 0 {
+            0.0
+        } else {
+            let downside_dev = (self.downside_sq_sum / self.downside_count as f64).sqrt();
+            if downside_dev == 0.0 {
+                0.0
+            } else {
+                ((self.equity_returns.mean - per_period_rf) / downside_dev) * self.config.periods_per_year.sqrt()
+            }
+        };
+
+        let total_trades = self.profit_count + self.loss_count;
+        let win_rate = if total_trades > 0 {
+            self.profit_count as f64 / total_trades as f64
+        } else {
+            0.0
+        };
+        let avg_profit = if self.profit_count > 0 {
+            self.profit_sum / self.profit_count as f64
+        } else {
+            0.0
+        };
+        let avg_loss = if self.loss_count > 0 {
+            self.loss_sum / self.loss_count as f64
+        } else {
+            0.0
+        };
+        let profit_loss_ratio = if avg_loss > 0.0 {
+            avg_profit / avg_loss
+        } else if avg_profit > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        let profit_factor = if self.loss_sum > 0.0 {
+            self.profit_sum / self.loss_sum
+        } else if self.profit_sum > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        let expectancy = win_rate * avg_profit - (1.0 - win_rate) * avg_loss;
+
+        let avg_holding_period_days = if self.round_trip_count > 0 {
+            self.total_holding_days / self.round_trip_count as f64
+        } else {
+            0.0
+        };
+
+        let (benchmark_return, benchmark_annualized_return, benchmark_max_dd, benchmark_max_dd_start, benchmark_max_dd_end) =
+            match (self.first_benchmark, self.last_benchmark) {
+                (Some((t0, e0)), Some((t1, e1))) if e0 != 0.0 => {
+                    let total_return = (e1 - e0) / e0;
+                    let days = if t1 > t0 { (t1 - t0).num_days() as f64 } else { 1.0 };
+                    let years = days / self.config.calendar_days_per_year;
+                    let annualized_return = if years > 0.0 {
+                        (e1 / e0).powf(1.0 / years) - 1.0
+                    } else {
+                        total_return
+                    };
+                    let (dd, dd_start, dd_end) = self.benchmark_drawdown.result();
+                    (Some(total_return), Some(annualized_return), Some(dd), dd_start, dd_end)
+                }
+                _ => (None, None, None, None, None),
+            };
+
+        let (beta, alpha, tracking_error, information_ratio) = if self.benchmark_relative.count >= 2 {
+            let var_y = self.benchmark_relative.var_y();
+            let beta = if var_y == 0.0 {
+                None
+            } else {
+                Some(self.benchmark_relative.covariance() / var_y)
+            };
+            let alpha = beta.map(|beta| {
+                (self.benchmark_relative.mean_x - beta * self.benchmark_relative.mean_y) * 252.0
+            });
+
+            let excess_variance = (self.benchmark_relative.var_x() + var_y
+                - 2.0 * self.benchmark_relative.covariance())
+            .max(0.0);
+            let std_excess = excess_variance.sqrt();
+            let tracking_error = Some(std_excess * (252.0_f64).sqrt());
+            let mean_excess = self.benchmark_relative.mean_x - self.benchmark_relative.mean_y;
+            let information_ratio = if std_excess == 0.0 {
+                None
+            } else {
+                Some(mean_excess / std_excess * (252.0_f64).sqrt())
+            };
+
+            (beta, alpha, tracking_error, information_ratio)
+        } else {
+            (None, None, None, None)
+        };
+
+        PerformanceStats {
+            total_return,
+            annualized_return,
+            max_drawdown,
+            max_drawdown_start: max_dd_start,
+            max_drawdown_end: max_dd_end,
+            sharpe_ratio,
+            win_rate,
+            profit_loss_ratio,
+            open_count: self.open_count,
+            close_count: self.close_count,
+            benchmark_return,
+            benchmark_annualized_return,
+            benchmark_max_drawdown: benchmark_max_dd,
+            benchmark_max_drawdown_start: benchmark_max_dd_start,
+            benchmark_max_drawdown_end: benchmark_max_dd_end,
+            sortino_ratio,
+            profit_factor,
+            expectancy,
+            beta,
+            alpha,
+            tracking_error,
+            information_ratio,
+            avg_holding_period_days,
+            largest_win: self.largest_win,
+            largest_loss: self.largest_loss,
+            max_win_streak: self.max_win_streak,
+            max_loss_streak: self.max_loss_streak,
+            avg_win: avg_profit,
+            avg_loss,
+            calmar_ratio: MetricsRecorder::calculate_calmar_ratio(annualized_return, max_drawdown),
+            trading_pnl: self.profit_sum - self.loss_sum,
+            // Patched in by `MetricsRecorder::calculate_stats`, which owns
+            // `total_funding_cost` outside the streaming/buffered split.
+            funding_pnl: 0.0,
+            total_funding_cost: 0.0,
+            total_commission: self.total_commission,
+            total_slippage: self.total_slippage,
+        }
+    }
+}
+
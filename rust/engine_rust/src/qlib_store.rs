@@ -0,0 +1,230 @@
+// Minimal reader/writer for a qlib-style on-disk market-data store: a shared
+// trading calendar, an instruments listing giving each symbol's valid date
+// range, and one little-endian f32 binary file per (symbol, field) holding a
+// start index into the calendar followed by that field's values for every
+// trading day from that index onward. This lets `DataFeed` be populated
+// straight from a pre-downloaded data directory (matching how qlib ships
+// bulk `cn_data`) instead of materializing every `Bar` as a Python object
+// first.
+//
+// Layout, relative to the dataset root:
+//   calendars/day.txt               one "YYYY-MM-DD" per line, ascending
+//   instruments/all.txt             "<symbol>\t<start_date>\t<end_date>" per line
+//   features/<symbol>/<field>.day.bin   f32 LE: [start_index, value, value, ...]
+
+use crate::types::Bar;
+use chrono::{NaiveDate, TimeZone, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const FIELDS: [&str; 5] = ["open", "high", "low", "close", "volume"];
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+fn calendar_path(dataset_dir: &Path) -> std::path::PathBuf {
+    dataset_dir.join("calendars").join("day.txt")
+}
+
+fn instruments_path(dataset_dir: &Path) -> std::path::PathBuf {
+    dataset_dir.join("instruments").join("all.txt")
+}
+
+fn feature_path(dataset_dir: &Path, symbol: &str, field: &str) -> std::path::PathBuf {
+    dataset_dir.join("features").join(symbol).join(format!("{}.day.bin", field))
+}
+
+/// Read the shared trading calendar (`calendars/day.txt`), ascending order.
+pub fn load_calendar(dataset_dir: &Path) -> io::Result<Vec<NaiveDate>> {
+    let text = fs::read_to_string(calendar_path(dataset_dir))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            NaiveDate::parse_from_str(line.trim(), DATE_FORMAT)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect()
+}
+
+/// Read the instrument listing (`instruments/all.txt`): symbol -> (first
+/// valid date, last valid date).
+pub fn load_instruments(dataset_dir: &Path) -> io::Result<HashMap<String, (NaiveDate, NaiveDate)>> {
+    let text = fs::read_to_string(instruments_path(dataset_dir))?;
+    let mut instruments = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let parts: Vec<&str> = line.trim().split('\t').collect();
+        if parts.len() != 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed instruments line: {:?}", line),
+            ));
+        }
+        let start = NaiveDate::parse_from_str(parts[1], DATE_FORMAT)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let end = NaiveDate::parse_from_str(parts[2], DATE_FORMAT)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        instruments.insert(parts[0].to_string(), (start, end));
+    }
+    Ok(instruments)
+}
+
+/// Read one (symbol, field) bin file into a calendar-index -> value map;
+/// missing files (a field that was never dumped for this symbol) yield an
+/// empty map rather than an error, since `fields` lets callers load a subset.
+fn load_field(dataset_dir: &Path, symbol: &str, field: &str) -> io::Result<HashMap<usize, f32>> {
+    let path = feature_path(dataset_dir, symbol, field);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    if bytes.len() < 4 || (bytes.len() - 4) % 4 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("truncated qlib bin file: {}", path.display()),
+        ));
+    }
+    let start_index = f32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let mut values = HashMap::with_capacity((bytes.len() - 4) / 4);
+    for (i, chunk) in bytes[4..].chunks_exact(4).enumerate() {
+        values.insert(start_index + i, f32::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    Ok(values)
+}
+
+/// Build `symbol`'s aligned `Bar` series for the optional `[start, end]`
+/// window, reading only the requested `fields` (any field not requested, or
+/// missing on disk, defaults to `0.0`). Rows outside the symbol's listed
+/// valid range in `instruments/all.txt` are skipped.
+pub fn load_symbol_bars(
+    dataset_dir: &Path,
+    symbol: &str,
+    calendar: &[NaiveDate],
+    instruments: &HashMap<String, (NaiveDate, NaiveDate)>,
+    fields: &[String],
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> io::Result<Vec<Bar>> {
+    let wanted: Vec<&str> = FIELDS.iter().filter(|f| fields.is_empty() || fields.iter().any(|w| w == *f)).copied().collect();
+    let mut columns: HashMap<&str, HashMap<usize, f32>> = HashMap::new();
+    for field in &wanted {
+        columns.insert(field, load_field(dataset_dir, symbol, field)?);
+    }
+
+    let (valid_start, valid_end) = instruments
+        .get(symbol)
+        .copied()
+        .unwrap_or((NaiveDate::MIN, NaiveDate::MAX));
+
+    let mut bars = Vec::new();
+    for (index, date) in calendar.iter().enumerate() {
+        if *date < valid_start || *date > valid_end {
+            continue;
+        }
+        if start.map_or(false, |s| *date < s) || end.map_or(false, |e| *date > e) {
+            continue;
+        }
+        let get = |field: &str| columns.get(field).and_then(|c| c.get(&index)).copied().unwrap_or(0.0) as f64;
+        let datetime = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+        bars.push(Bar {
+            datetime,
+            open: get("open"),
+            high: get("high"),
+            low: get("low"),
+            close: get("close"),
+            volume: get("volume"),
+        });
+    }
+    Ok(bars)
+}
+
+/// Load every requested `symbols` (or every listed instrument, if empty)
+/// from `dataset_dir` over the optional `[start, end]` window, reading only
+/// `fields` (empty means all of open/high/low/close/volume).
+pub fn load_dataset(
+    dataset_dir: &Path,
+    symbols: &[String],
+    fields: &[String],
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> io::Result<HashMap<String, Vec<Bar>>> {
+    let calendar = load_calendar(dataset_dir)?;
+    let instruments = load_instruments(dataset_dir)?;
+    let symbol_list: Vec<String> = if symbols.is_empty() {
+        instruments.keys().cloned().collect()
+    } else {
+        symbols.to_vec()
+    };
+
+    let mut result = HashMap::with_capacity(symbol_list.len());
+    for symbol in symbol_list {
+        let bars = load_symbol_bars(dataset_dir, &symbol, &calendar, &instruments, fields, start, end)?;
+        result.insert(symbol, bars);
+    }
+    Ok(result)
+}
+
+/// Dump `data` (symbol -> bars, already sorted by `add_market_data`'s
+/// convention) to `dataset_dir` in the layout `load_dataset` reads back: a
+/// calendar built from the union of every bar's date, an instrument listing
+/// giving each symbol's observed first/last date, and one bin file per
+/// (symbol, field). Assumes each symbol's bars are dense over its own
+/// `[first_date, last_date]` span (no gaps relative to the written
+/// calendar) — the same assumption qlib's own dumper makes for a single
+/// trading venue.
+pub fn write_dataset(dataset_dir: &Path, data: &HashMap<String, Vec<Bar>>) -> io::Result<()> {
+    let mut calendar: Vec<NaiveDate> = data
+        .values()
+        .flat_map(|bars| bars.iter().map(|bar| bar.datetime.date_naive()))
+        .collect();
+    calendar.sort();
+    calendar.dedup();
+    let calendar_index: HashMap<NaiveDate, usize> = calendar.iter().enumerate().map(|(i, d)| (*d, i)).collect();
+
+    fs::create_dir_all(dataset_dir.join("calendars"))?;
+    let mut calendar_file = fs::File::create(calendar_path(dataset_dir))?;
+    for date in &calendar {
+        writeln!(calendar_file, "{}", date.format(DATE_FORMAT))?;
+    }
+
+    fs::create_dir_all(dataset_dir.join("instruments"))?;
+    let mut instruments_file = fs::File::create(instruments_path(dataset_dir))?;
+
+    for (symbol, bars) in data {
+        if bars.is_empty() {
+            continue;
+        }
+        let first_date = bars.first().unwrap().datetime.date_naive();
+        let last_date = bars.last().unwrap().datetime.date_naive();
+        writeln!(
+            instruments_file,
+            "{}\t{}\t{}",
+            symbol,
+            first_date.format(DATE_FORMAT),
+            last_date.format(DATE_FORMAT)
+        )?;
+
+        let symbol_dir = dataset_dir.join("features").join(symbol);
+        fs::create_dir_all(&symbol_dir)?;
+        let start_index = calendar_index[&first_date];
+
+        for field in FIELDS {
+            let mut bytes = Vec::with_capacity(4 + bars.len() * 4);
+            bytes.extend_from_slice(&(start_index as f32).to_le_bytes());
+            for bar in bars {
+                let value = match field {
+                    "open" => bar.open,
+                    "high" => bar.high,
+                    "low" => bar.low,
+                    "close" => bar.close,
+                    "volume" => bar.volume,
+                    _ => unreachable!(),
+                } as f32;
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            fs::write(feature_path(dataset_dir, symbol, field), bytes)?;
+        }
+    }
+
+    Ok(())
+}
@@ -29,6 +29,22 @@ pub enum QuantityType {
     Weight, // Target portfolio weight (0.0 - 1.0)
 }
 
+/// Order type: determines whether/how an order is marketable within a bar
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderType {
+    Market,
+    Limit { limit_price: f64 },
+    Stop { stop_price: f64 },
+    StopLimit { stop_price: f64, limit_price: f64 },
+}
+
+/// Time in force: how long a resting (non-market) order stays live
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeInForce {
+    Day,
+    Gtc,
+}
+
 /// Order structure
 #[derive(Clone, Debug)]
 pub struct Order {
@@ -37,6 +53,33 @@ pub struct Order {
     pub quantity_type: QuantityType,
     pub quantity: f64,
     pub timestamp: DateTime<chrono::Utc>,
+    pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    /// Lots already filled from this order on a prior bar (partial fills under a
+    /// participation-rate cap accumulate here as the remainder rests and refills).
+    pub filled_quantity: f64,
+}
+
+impl Order {
+    /// Convenience constructor for a plain market order (the previous default behavior)
+    pub fn market(
+        symbol: String,
+        side: OrderSide,
+        quantity_type: QuantityType,
+        quantity: f64,
+        timestamp: DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            symbol,
+            side,
+            quantity_type,
+            quantity,
+            timestamp,
+            order_type: OrderType::Market,
+            time_in_force: TimeInForce::Day,
+            filled_quantity: 0.0,
+        }
+    }
 }
 
 /// Fill (executed trade) structure
@@ -47,17 +90,91 @@ pub struct Fill {
     pub quantity: f64,      // In lots
     pub price: f64,
     pub commission: f64,
+    /// Cash cost of the configured `SlippageModel`: how much worse this
+    /// fill's price was than the pre-slippage marketable price,
+    /// signed the same way as `commission` (positive erodes P&L).
+    pub slippage: f64,
     pub timestamp: DateTime<chrono::Utc>,
+    /// Net realized P&L closed out by this fill (0 for a fill that only opens
+    /// or extends exposure), already net of this fill's commission.
+    pub realized_pnl: f64,
+}
+
+/// A single matched round trip produced by FIFO-draining one buy lot against
+/// a (possibly later, possibly partial) sell. One sell fill that drains
+/// multiple lots yields one `RoundTrip` per lot.
+#[derive(Clone, Debug)]
+pub struct RoundTrip {
+    pub symbol: String,
+    /// `"long"` for a buy-then-sell lot, `"short"` for a sell-then-buy lot.
+    pub side: String,
+    pub entry_time: DateTime<chrono::Utc>,
+    pub entry_price: f64,
+    pub exit_time: DateTime<chrono::Utc>,
+    pub exit_price: f64,
+    pub quantity: f64, // In lots
+    pub realized_pnl: f64,
+    pub return_pct: f64,
+    pub holding_period: chrono::Duration,
+    /// Entry- and exit-fill commission prorated to this lot's share of each
+    /// fill's total quantity.
+    pub commission: f64,
 }
 
 /// Position information
+///
+/// `quantity` may be negative to represent a short position. `init_margin` and
+/// `maint_margin` are the fractions of notional exposure that must be backed by
+/// equity at order time and on every subsequent mark, respectively.
 #[derive(Clone, Debug)]
 pub struct Position {
     pub symbol: String,
-    pub quantity: f64,        // Total position in lots
+    pub quantity: f64,        // Total position in lots (negative = short)
     pub avg_cost: f64,       // Average cost price
     pub market_value: f64,   // Current market value
     pub available: f64,      // Available quantity (considering T+1)
+    pub init_margin: f64,    // Initial margin fraction required to open/increase exposure
+    pub maint_margin: f64,   // Maintenance margin fraction required to keep exposure open
+}
+
+/// One side of a protective-exit trigger: an absolute price, or a percent
+/// magnitude applied relative to the position's average cost (direction is
+/// resolved by the caller depending on whether it's a stop-loss or a
+/// take-profit, and on whether the position is long or short).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitTrigger {
+    Price(f64),
+    Percent(f64),
+}
+
+/// Protective exits attached to one symbol's position, checked before
+/// `on_bar` runs (price triggers against each bar's intrabar high/low, the
+/// ROI schedule against its close) so strategies don't have to
+/// re-check risk management in Python every bar (freqtrade's ROI table /
+/// stoploss / trailing-stop, evaluated natively instead).
+#[derive(Clone, Debug, Default)]
+pub struct ExitPlan {
+    pub stop_loss: Option<ExitTrigger>,
+    pub take_profit: Option<ExitTrigger>,
+    /// Trailing-stop distance as a fraction of the best price seen since
+    /// entry (e.g. `0.05` exits 5% below the peak for a long, 5% above the
+    /// trough for a short).
+    pub trailing_stop_pct: Option<f64>,
+    /// `(bars_held, min_return)` pairs. The entry with the largest
+    /// `bars_held` not exceeding the position's current age applies; once
+    /// the position's return reaches that entry's `min_return`, it is force-
+    /// closed. Mirrors freqtrade's ROI table.
+    pub roi_schedule: Vec<(usize, f64)>,
+}
+
+/// Runtime state for one symbol's `ExitPlan`, reset whenever the position
+/// returns to flat.
+#[derive(Clone, Debug)]
+pub struct ExitState {
+    pub opened_at_bar: usize,
+    /// High-water mark for a long, low-water mark for a short; drives the
+    /// trailing stop.
+    pub extreme_price: f64,
 }
 
 /// Buy record for T+1 tracking
@@ -76,6 +193,12 @@ pub struct PortfolioState {
     pub buy_records: HashMap<String, Vec<BuyRecord>>, // For T+1 tracking
     pub fills: Vec<Fill>,
     pub t0_symbols: Vec<String>, // Symbols that support T+0
+    /// Open cost-basis lots per symbol, FIFO-ordered, independent of `buy_records`
+    /// (which is pruned daily for T+1 purposes and can't double as a cost ledger).
+    /// A positive `quantity` is a long lot, a negative `quantity` is a short lot.
+    pub open_lots: HashMap<String, Vec<BuyRecord>>,
+    /// Cumulative realized P&L across all fills, net of commission.
+    pub realized_pnl: f64,
 }
 
 impl PortfolioState {
@@ -86,6 +209,8 @@ impl PortfolioState {
             buy_records: HashMap::new(),
             fills: Vec::new(),
             t0_symbols,
+            open_lots: HashMap::new(),
+            realized_pnl: 0.0,
         }
     }
 
@@ -96,6 +221,12 @@ impl PortfolioState {
             None => return 0.0,
         };
 
+        // A short position has nothing subject to T+1: covering it is a buy,
+        // which `get_available` (a sell-side concept) never gates.
+        if position < 0.0 {
+            return position;
+        }
+
         // T+0 symbols: available = position
         if self.t0_symbols.contains(&symbol.to_string()) {
             return position;
@@ -140,6 +271,40 @@ impl PortfolioState {
             records.retain(|r| r.date == current_date);
         }
     }
+
+    /// Account health at the given margin level:
+    /// `equity - sum_over_positions(|market_value| * margin_fraction)`.
+    /// A negative value means the account does not meet that margin requirement
+    /// at current prices (a short's market value is negative; its risk is
+    /// measured by absolute notional, same as a long).
+    pub fn account_health(&self, current_prices: &HashMap<String, f64>, level: MarginLevel) -> f64 {
+        let margin_requirement: f64 = self
+            .positions
+            .values()
+            .map(|pos| {
+                let price = current_prices
+                    .get(&pos.symbol)
+                    .copied()
+                    .unwrap_or(pos.avg_cost);
+                let market_value = pos.quantity * price * 100.0;
+                let fraction = match level {
+                    MarginLevel::Init => pos.init_margin,
+                    MarginLevel::Maint => pos.maint_margin,
+                };
+                market_value.abs() * fraction
+            })
+            .sum();
+
+        self.calculate_equity(current_prices) - margin_requirement
+    }
+}
+
+/// Which margin regime a health computation applies: initial margin gates new
+/// orders, maintenance margin gates whether open exposure must be liquidated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarginLevel {
+    Init,
+    Maint,
 }
 
 /// Equity curve point
@@ -162,5 +327,103 @@ pub struct PerformanceStats {
     pub profit_loss_ratio: f64,
     pub open_count: usize,   // 开仓次数（买入成交次数）
     pub close_count: usize,  // 平仓次数（卖出成交次数）
+    pub benchmark_return: Option<f64>,
+    pub benchmark_annualized_return: Option<f64>,
+    pub benchmark_max_drawdown: Option<f64>,
+    pub benchmark_max_drawdown_start: Option<DateTime<chrono::Utc>>,
+    pub benchmark_max_drawdown_end: Option<DateTime<chrono::Utc>>,
+    /// Downside-deviation-adjusted counterpart to `sharpe_ratio`: 0 when the
+    /// return series has no downside deviation to penalize.
+    pub sortino_ratio: f64,
+    /// Gross profit over gross loss across closed trades; `f64::INFINITY` when
+    /// there was gross profit but no gross loss, 0 when there was neither.
+    pub profit_factor: f64,
+    /// Expected P&L per trade: `win_rate * avg_profit - (1 - win_rate) * avg_loss`.
+    pub expectancy: f64,
+    /// Sensitivity of strategy returns to benchmark returns: `cov(strategy,
+    /// benchmark) / var(benchmark)`. `None` when there's no aligned benchmark.
+    pub beta: Option<f64>,
+    /// Annualized excess return not explained by `beta`: `(mean_strategy -
+    /// beta * mean_benchmark) * 252`.
+    pub alpha: Option<f64>,
+    /// Annualized standard deviation of `strategy - benchmark` returns.
+    pub tracking_error: Option<f64>,
+    /// Annualized mean excess return over `tracking_error`.
+    pub information_ratio: Option<f64>,
+    /// Mean holding period across `RoundTrip`s, in days.
+    pub avg_holding_period_days: f64,
+    /// Largest single `RoundTrip` realized P&L (0 if there were no round trips).
+    pub largest_win: f64,
+    /// Largest single `RoundTrip` realized loss, as a positive magnitude (0 if
+    /// there were no losing round trips).
+    pub largest_loss: f64,
+    /// Longest run of consecutive winning round trips in exit-time order.
+    pub max_win_streak: usize,
+    /// Longest run of consecutive losing round trips in exit-time order.
+    pub max_loss_streak: usize,
+    /// Mean realized P&L of winning round trips (0 if there were none).
+    pub avg_win: f64,
+    /// Mean realized P&L magnitude of losing round trips, as a positive
+    /// number (0 if there were none).
+    pub avg_loss: f64,
+    /// `annualized_return / max_drawdown`; `f64::INFINITY` when there was no
+    /// drawdown and a positive return, 0 otherwise.
+    pub calmar_ratio: f64,
+    /// Realized P&L from trading (FIFO round trips), excluding funding.
+    pub trading_pnl: f64,
+    /// Net perpetual-futures funding received (negative means net paid);
+    /// `-total_funding_cost`. 0 when futures mode is off.
+    pub funding_pnl: f64,
+    /// Net funding charged against cash across the backtest (a long-heavy
+    /// book with a positive average rate pays, so this is usually positive).
+    pub total_funding_cost: f64,
+    /// Sum of every fill's `commission`.
+    pub total_commission: f64,
+    /// Sum of every fill's `slippage`.
+    pub total_slippage: f64,
+}
+
+/// Annualization basis for `MetricsRecorder`'s ratio and return calculations,
+/// so callers on intraday, weekly, or crypto (365-day) data aren't stuck with
+/// the daily-equities assumptions baked into `Default`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetricsConfig {
+    /// Annual risk-free rate, subtracted per-period before computing excess
+    /// mean return in the Sharpe and Sortino ratios.
+    pub risk_free_rate: f64,
+    /// Return observations per year (e.g. 252 for daily equities, 365 for
+    /// crypto), used to convert `risk_free_rate` to a per-period rate and to
+    /// scale Sharpe/Sortino up to an annualized figure.
+    pub periods_per_year: f64,
+    /// Calendar days per year used for the `annualized_return` exponent.
+    pub calendar_days_per_year: f64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            risk_free_rate: 0.0,
+            periods_per_year: 252.0,
+            calendar_days_per_year: 365.25,
+        }
+    }
+}
+
+/// Bucketing granularity for `MetricsRecorder::calculate_periodic_stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    Yearly,
+    Monthly,
+}
+
+/// Performance summary for a single calendar bucket (year or month).
+#[derive(Clone, Debug)]
+pub struct PeriodStats {
+    /// `"2024"` for `Period::Yearly`, `"2024-03"` for `Period::Monthly`.
+    pub label: String,
+    pub return_pct: f64,
+    pub max_drawdown: f64,
+    pub benchmark_return: Option<f64>,
+    pub excess_return: Option<f64>,
 }
 
@@ -0,0 +1,81 @@
+// UniverseFilter: pluggable per-rebalance symbol eligibility filtering,
+// borrowing freqtrade's pairlist-handler concept so a strategy doesn't have
+// to re-derive "which symbols are tradable today" in Python every bar.
+
+use crate::types::Bar;
+use std::collections::HashMap;
+
+/// One stage of a `UniverseFilter` chain, applied in order against the
+/// previous stage's surviving symbol set. Tradability (a missing current
+/// `Bar`, e.g. a halted or delisted symbol) is *not* one of these — it is
+/// always re-checked every bar regardless of cadence, since a halt can't
+/// wait for the next scheduled rebalance.
+#[derive(Clone, Debug)]
+pub enum UniverseFilter {
+    /// Drop symbols whose current close is below `min_price`.
+    MinPrice { min_price: f64 },
+    /// Drop symbols whose average volume over the trailing `lookback` bars
+    /// is below `min_volume`.
+    MinAverageVolume { lookback: usize, min_volume: f64 },
+    /// Keep only the `n` highest-ranked symbols by a named indicator's
+    /// current value (descending); symbols with no value for that indicator
+    /// are dropped rather than ranked.
+    TopNByIndicator { indicator: String, n: usize },
+}
+
+/// How often the `UniverseFilter` chain is recomputed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniverseCadence {
+    EveryBar,
+    Daily,
+    Weekly,
+}
+
+/// Narrow `candidates` to the symbols surviving every filter in `filters`, in
+/// order. `current_bars` anchors price-based checks to this rebalance's
+/// prices; `bars_for(symbol, lookback)` resolves a trailing volume window;
+/// `indicator_value(symbol, name)` resolves a named indicator's current
+/// value.
+pub fn apply_filters(
+    filters: &[UniverseFilter],
+    candidates: &[String],
+    current_bars: &HashMap<String, Bar>,
+    bars_for: impl Fn(&str, usize) -> Vec<Bar>,
+    indicator_value: impl Fn(&str, &str) -> Option<f64>,
+) -> Vec<String> {
+    let mut universe: Vec<String> = candidates.to_vec();
+
+    for filter in filters {
+        universe = match filter {
+            UniverseFilter::MinPrice { min_price } => universe
+                .into_iter()
+                .filter(|symbol| current_bars.get(symbol).map_or(false, |bar| bar.close >= *min_price))
+                .collect(),
+            UniverseFilter::MinAverageVolume { lookback, min_volume } => universe
+                .into_iter()
+                .filter(|symbol| {
+                    let bars = bars_for(symbol, *lookback);
+                    if bars.is_empty() {
+                        return false;
+                    }
+                    let avg_volume = bars.iter().map(|bar| bar.volume).sum::<f64>() / bars.len() as f64;
+                    avg_volume >= *min_volume
+                })
+                .collect(),
+            UniverseFilter::TopNByIndicator { indicator, n } => {
+                let mut ranked: Vec<(String, f64)> = universe
+                    .into_iter()
+                    .filter_map(|symbol| {
+                        let value = indicator_value(&symbol, indicator)?;
+                        Some((symbol, value))
+                    })
+                    .collect();
+                ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+                ranked.truncate(*n);
+                ranked.into_iter().map(|(symbol, _)| symbol).collect()
+            }
+        };
+    }
+
+    universe
+}